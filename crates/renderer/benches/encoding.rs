@@ -0,0 +1,97 @@
+//! Compares the size and speed of each encoding on the renders of the bundled model
+//!
+//! Run with `cargo bench -p renderer`
+
+use std::time::{Duration, Instant};
+
+use geometry::Vec3f;
+use renderer::{colors::*, Draw};
+use tga_image as tga;
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 800;
+
+fn screen(v: Vec3f) -> Vec3f {
+    Vec3f::new3(
+        (v[0] + 1.0) * WIDTH as f32 * 0.5,
+        (v[1] + 1.0) * HEIGHT as f32 * 0.5,
+        (v[2] + 1.0) * 0.5,
+    )
+}
+
+fn wireframe(model: &obj::Model) -> tga::Image {
+    let verts = model.verts();
+    let mut image = tga::Image::new(WIDTH, HEIGHT, 3);
+
+    for face in model.faces() {
+        for i in 0..face.len() {
+            let v0 = screen(verts[face[i]]);
+            let v1 = screen(verts[face[(i + 1) % face.len()]]);
+            image.draw_line(
+                [v0[0] as isize, v0[1] as isize],
+                [v1[0] as isize, v1[1] as isize],
+                WHITE,
+            );
+        }
+    }
+    image
+}
+
+fn flat_shading(model: &obj::Model) -> tga::Image {
+    let verts = model.verts();
+    let mut image = tga::Image::new(WIDTH, HEIGHT, 3);
+    let mut z_buffer = vec![0f32; WIDTH * HEIGHT];
+    let light_dir = Vec3f::new3(0.0, 0.0, -1.0);
+
+    for face in model.faces() {
+        let world_coords = [verts[face[0]], verts[face[1]], verts[face[2]]];
+        let screen_coords = world_coords.map(screen);
+
+        let n = (world_coords[2] - world_coords[0]).cross(world_coords[1] - world_coords[0]);
+        let intensity = (n * (1.0 / n.len())).dot(light_dir);
+        if intensity > 0.0 {
+            let v = (intensity * 255.0) as u8;
+            let color = tga::Color::rgb(v, v, v);
+            renderer::triangle_with_depth_test(&mut image, screen_coords, &mut z_buffer, color);
+        }
+    }
+    image
+}
+
+/// The size of the file and the average time to write it
+fn bench(image: &tga::Image, encoding: Option<tga::Encoding>) -> (usize, Duration) {
+    const ITERATIONS: u32 = 10;
+
+    let mut size = 0;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        size = image.to_bytes(encoding.clone()).unwrap().len();
+    }
+    (size, start.elapsed() / ITERATIONS)
+}
+
+fn main() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../objs/african_head.obj");
+    let model = obj::Model::from_file(path).unwrap();
+
+    let renders = [
+        ("wireframe", wireframe(&model)),
+        ("flat_shading", flat_shading(&model)),
+    ];
+    let encodings = [
+        ("raw", None),
+        ("rle", Some(tga::Encoding::Rle)),
+        ("rle_scanline", Some(tga::Encoding::RleScanline)),
+        ("auto", Some(tga::Encoding::Auto)),
+    ];
+
+    for (render, image) in &renders {
+        for (name, encoding) in &encodings {
+            let (size, time) = bench(image, encoding.clone());
+            println!(
+                "{:<13} {:<13} {:>9} bytes {:>10.3?}",
+                render, name, size, time
+            );
+        }
+    }
+}
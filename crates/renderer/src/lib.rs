@@ -47,7 +47,6 @@ pub fn triangle(image: &mut tga::Image, points: [Vec2i; 3], color: tga::Color) {
             let bc_screen = barycentric(points, Vec2i::new2(x, y));
             if bc_screen[0] >= 0.0 && bc_screen[1] >= 0.0 && bc_screen[2] >= 0.0 {
                 image.set(x as usize, y as usize, color);
-            } else {
             }
         }
     }
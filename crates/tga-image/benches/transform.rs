@@ -0,0 +1,46 @@
+//! Times flipping and rotating an 8K image
+//!
+//! Run with `cargo bench -p tga-image`
+
+use std::time::{Duration, Instant};
+
+use tga_image::Image;
+
+const WIDTH: usize = 7680;
+const HEIGHT: usize = 4320;
+
+type Transform = fn(&mut Image);
+
+/// The average time to transform the image
+fn bench(image: &mut Image, transform: Transform) -> Duration {
+    const ITERATIONS: u32 = 4;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        transform(image);
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let transforms: [(&str, Transform); 6] = [
+        ("flip_horizontally", Image::flip_horizontally),
+        ("flip_vertically", Image::flip_vertically),
+        ("rotate90", Image::rotate90),
+        ("rotate180", Image::rotate180),
+        ("rotate270", Image::rotate270),
+        ("transpose", Image::transpose),
+    ];
+
+    for &bytes_pp in &[1, 3, 4] {
+        let mut image = Image::new(WIDTH, HEIGHT, bytes_pp);
+        for (i, byte) in image.as_mut_slice().iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+
+        for (name, transform) in &transforms {
+            let time = bench(&mut image, *transform);
+            println!("{} bytes per pixel {:<18} {:>10.3?}", bytes_pp, name, time);
+        }
+    }
+}
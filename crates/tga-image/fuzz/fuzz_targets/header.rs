@@ -0,0 +1,35 @@
+//! Reads whole files, mostly exercising the header, color map and footer
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tga_image::{DecodeLimits, Image, ReadOptions, TgaDecoder};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = DecodeLimits::new().max_bytes(1 << 24);
+
+    let options = ReadOptions::new().limits(limits);
+    let _ = Image::from_seekable_reader_with_options(std::io::Cursor::new(data), &options);
+    let _ = Image::from_seekable_reader_lenient(std::io::Cursor::new(data), &options);
+    let _ = Image::thumbnail_from_seekable_reader(std::io::Cursor::new(data));
+
+    if let Ok(mut decoder) = TgaDecoder::with_limits(data, limits) {
+        while let Ok(Some(_)) = decoder.read_row() {}
+    }
+
+    // the data as the developer area of a 1x1 grey scale image, its fields can point anywhere
+    let mut file = vec![0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 8, 0, 128];
+    file.extend_from_slice(data);
+    file.extend_from_slice(&[0, 0, 0, 0, 19, 0, 0, 0]);
+    file.extend_from_slice(b"TRUEVISION-XFILE.\0");
+    if let Ok((image, _)) =
+        Image::from_seekable_reader_lenient(std::io::Cursor::new(&file), &options)
+    {
+        let size: usize = image
+            .developer_area()
+            .iter()
+            .map(|(_, data)| data.len())
+            .sum();
+        assert!(size <= file.len());
+    }
+});
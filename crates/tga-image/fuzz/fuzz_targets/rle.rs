@@ -0,0 +1,43 @@
+//! Decodes arbitrary run length packets behind a valid header
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tga_image::{Image, TgaDecoder};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+
+    let (width, height) = (data[0] % 64 + 1, data[1] % 64 + 1);
+    let (data_type_code, bits_per_pixel) = match data[2] % 4 {
+        0 => (11, 8),
+        1 => (10, 16),
+        2 => (10, 24),
+        _ => (10, 32),
+    };
+
+    let mut file = vec![0, 0, data_type_code, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    file.extend_from_slice(&[width, 0, height, 0, bits_per_pixel, 0x20]);
+    file.extend_from_slice(&data[3..]);
+
+    let image = Image::from_reader(&file[..]);
+
+    let mut decoder = TgaDecoder::new(&file[..]).expect("the header is valid");
+    let mut rows = Vec::new();
+    let decoded = loop {
+        match decoder.read_row() {
+            Ok(Some(row)) => rows.extend_from_slice(row),
+            Ok(None) => break Ok(rows),
+            Err(error) => break Err(error),
+        }
+    };
+
+    // both paths share the packet logic so they must agree
+    match (image, decoded) {
+        (Ok(image), Ok(rows)) => assert_eq!(image.as_slice(), &rows[..]),
+        (Err(_), Err(_)) => {}
+        (image, rows) => panic!("{:?} != {:?}", image.map(|_| ()), rows.map(|_| ())),
+    }
+});
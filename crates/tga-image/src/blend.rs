@@ -0,0 +1,364 @@
+//! Compositing colors and images with alpha
+
+use crate::{AttributesType, Color, Image, LumaWeights};
+
+/// How the color channels of a pixel relate to its alpha
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// - Straight: the color channels are independent of the alpha
+    #[default]
+    Straight,
+    /// - Premultiplied: the color channels have already been multiplied by the alpha
+    Premultiplied,
+    /// - Undefined: the alpha isn't known to mean anything, it is blended like straight alpha
+    Undefined,
+}
+
+impl AlphaMode {
+    /// The alpha mode of a stored image, `alpha_bits` is from its descriptor
+    pub(crate) fn from_alpha_bits(alpha_bits: u8, bytes_pp: usize) -> Self {
+        if bytes_pp == 4 && alpha_bits == 0 {
+            Self::Undefined
+        } else {
+            Self::Straight
+        }
+    }
+
+    /// The alpha mode described by the attributes type of the metadata, if it describes one
+    pub(crate) fn from_attributes_type(attributes_type: AttributesType) -> Option<Self> {
+        match attributes_type {
+            AttributesType::UndefinedIgnore | AttributesType::UndefinedRetain => {
+                Some(Self::Undefined)
+            }
+            AttributesType::Alpha => Some(Self::Straight),
+            AttributesType::PremultipliedAlpha => Some(Self::Premultiplied),
+            _ => None,
+        }
+    }
+
+    /// The attributes type to write, `current` is kept if it already matches
+    pub(crate) fn attributes_type(
+        self,
+        has_alpha: bool,
+        current: AttributesType,
+    ) -> AttributesType {
+        match self {
+            _ if !has_alpha => AttributesType::NoAlpha,
+            Self::Straight => AttributesType::Alpha,
+            Self::Premultiplied => AttributesType::PremultipliedAlpha,
+            Self::Undefined => match current {
+                AttributesType::UndefinedIgnore => current,
+                _ => AttributesType::UndefinedRetain,
+            },
+        }
+    }
+}
+
+/// # How a source color is combined with the destination color under it
+///
+/// The Porter-Duff operators decide which parts of each color are kept, the blend modes mix
+/// the colors where they overlap and are otherwise like [`BlendMode::Over`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// - Over: the source on top of the destination
+    Over,
+    /// - In: the source where the destination is
+    In,
+    /// - Out: the source where the destination isn't
+    Out,
+    /// - Atop: the source on top of the destination, only where the destination is
+    Atop,
+    /// - Xor: the source and the destination where they don't overlap
+    Xor,
+    /// - Multiply: the product of the colors, always darker
+    Multiply,
+    /// - Screen: the inverse of the product of the inverse colors, always lighter
+    Screen,
+    /// - Overlay: multiply where the destination is dark and screen where it is light
+    Overlay,
+    /// - Add: the sum of the colors and alphas, for lights and glows
+    Add,
+}
+
+impl BlendMode {
+    /// Mixes a source and destination channel where they overlap, with straight alpha
+    fn mix(self, source: f32, destination: f32) -> f32 {
+        match self {
+            Self::Multiply => source * destination,
+            Self::Screen => source + destination - source * destination,
+            Self::Overlay if destination <= 0.5 => 2.0 * source * destination,
+            Self::Overlay => 1.0 - 2.0 * (1.0 - source) * (1.0 - destination),
+            _ => source,
+        }
+    }
+}
+
+impl Color {
+    /// Multiplies the color channels by the alpha, a color without alpha is unchanged
+    pub fn premultiplied(self) -> Self {
+        if self.bytes_pp != 4 {
+            return self;
+        }
+        let a = self.bgra[3] as u32;
+        let scale = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+        Self::rgba(scale(self.r()), scale(self.g()), scale(self.b()), a as u8)
+    }
+
+    /// Divides the color channels by the alpha, a color without alpha is unchanged and a
+    /// transparent color becomes transparent black
+    pub fn unpremultiplied(self) -> Self {
+        if self.bytes_pp != 4 {
+            return self;
+        }
+        let a = self.bgra[3] as u32;
+        if a == 0 {
+            return Self::rgba(0, 0, 0, 0);
+        }
+        let scale = |c: u8| ((c as u32 * 255 + a / 2) / a).min(255) as u8;
+        Self::rgba(scale(self.r()), scale(self.g()), scale(self.b()), a as u8)
+    }
+
+    /// Combines this color with the `destination` color under it
+    ///
+    /// Both colors use `alpha` and a color without alpha is opaque.
+    ///
+    /// ## returns
+    ///
+    /// - a color in the format of `destination`, if it has no alpha the result is as if it was
+    ///   over black
+    pub fn blend(self, destination: Color, mode: BlendMode, alpha: AlphaMode) -> Self {
+        let premultiplied = |color: Color| {
+            let color = match alpha {
+                AlphaMode::Premultiplied => color,
+                _ => color.premultiplied(),
+            };
+            [color.r(), color.g(), color.b(), color.a()].map(|c| c as f32 / 255.0)
+        };
+        let [sr, sg, sb, sa] = premultiplied(self);
+        let [dr, dg, db, da] = premultiplied(destination);
+
+        let (source_weight, destination_weight, a) = match mode {
+            BlendMode::In => (da, 0.0, sa * da),
+            BlendMode::Out => (1.0 - da, 0.0, sa * (1.0 - da)),
+            BlendMode::Atop => (da, 1.0 - sa, da),
+            BlendMode::Xor => (1.0 - da, 1.0 - sa, sa * (1.0 - da) + da * (1.0 - sa)),
+            BlendMode::Add => (1.0, 1.0, (sa + da).min(1.0)),
+            _ => (1.0, 1.0 - sa, sa + da * (1.0 - sa)),
+        };
+
+        let straight = |c: f32, a: f32| if a == 0.0 { 0.0 } else { c / a };
+        let channel = |s: f32, d: f32| {
+            let mut c = s * source_weight + d * destination_weight;
+            if let BlendMode::Multiply | BlendMode::Screen | BlendMode::Overlay = mode {
+                // the overlap is mixed rather than covered by the source
+                c += sa * da * (mode.mix(straight(s, sa), straight(d, da)) - straight(s, sa));
+            }
+            c.clamp(0.0, 1.0)
+        };
+        let [r, g, b] = [channel(sr, dr), channel(sg, dg), channel(sb, db)];
+
+        let byte = |c: f32| (c * 255.0).round() as u8;
+        let result = match (destination.bytes_pp, alpha) {
+            (4, AlphaMode::Premultiplied) => Self::rgba(byte(r), byte(g), byte(b), byte(a)),
+            (4, _) => Self::rgba(
+                byte(straight(r, a)),
+                byte(straight(g, a)),
+                byte(straight(b, a)),
+                byte(a),
+            ),
+            _ => Self::rgb(byte(r), byte(g), byte(b)),
+        };
+        match destination.bytes_pp {
+            1 => result.to_grey_scale(LumaWeights::default()),
+            _ => result,
+        }
+    }
+}
+
+impl Image {
+    /// How the color channels of the pixels relate to their alpha
+    ///
+    /// Images read from a file use the attributes type of the metadata, or the alpha bits of the
+    /// header if there is none. When the image is written this decides the attributes type,
+    /// rather than the metadata.
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Sets how the color channels of the pixels relate to their alpha, the pixels aren't
+    /// changed
+    pub fn set_alpha_mode(&mut self, alpha_mode: AlphaMode) {
+        self.alpha_mode = alpha_mode;
+    }
+
+    /// Multiplies the color channels of each pixel by its alpha, unless they already are
+    pub fn premultiply(&mut self) {
+        if self.alpha_mode != AlphaMode::Premultiplied {
+            self.map_colors(Color::premultiplied);
+            self.alpha_mode = AlphaMode::Premultiplied;
+        }
+    }
+
+    /// Divides the color channels of each pixel by its alpha, if they are premultiplied
+    pub fn unpremultiply(&mut self) {
+        if self.alpha_mode == AlphaMode::Premultiplied {
+            self.map_colors(Color::unpremultiplied);
+            self.alpha_mode = AlphaMode::Straight;
+        }
+    }
+
+    /// Replaces each pixel with `f` of it, if it has alpha
+    fn map_colors(&mut self, f: fn(Color) -> Color) {
+        if self.bytes_pp != 4 {
+            return;
+        }
+        for pixel in self.data.chunks_exact_mut(4) {
+            let color = Color::try_from_slice(pixel).unwrap_or_default();
+            pixel.copy_from_slice(f(color).as_slice());
+        }
+    }
+
+    /// Combines a color with the pixel under it, the color uses the alpha mode of the image
+    ///
+    /// ## returns
+    ///
+    /// - true if the pixel is inside the image
+    /// - false otherwise
+    pub fn blend_pixel(&mut self, x: usize, y: usize, color: Color, mode: BlendMode) -> bool {
+        match self.get(x, y) {
+            Some(destination) => {
+                let blended = color.blend(destination, mode, self.alpha_mode);
+                self.set(x, y, blended)
+            }
+            None => false,
+        }
+    }
+
+    /// Combines `other` with this image, its first pixel is put at `x`, `y`
+    ///
+    /// `x` and `y` are in the order the pixels are stored, the same as [`Image::get`] and
+    /// [`Image::copy_from`], and can be negative. `other` is flipped to the orientation of this
+    /// image and its pixels are converted to the alpha mode of this image, the parts outside this
+    /// image are ignored.
+    pub fn composite(&mut self, other: &Image, x: isize, y: isize, mode: BlendMode) {
+        let mut reoriented;
+        let other = if other.orientation == self.orientation {
+            other
+        } else {
+            reoriented = other.clone();
+            reoriented.reorient(self.orientation);
+            &reoriented
+        };
+
+        for j in 0..other.height {
+            let y = y + j as isize;
+            if y < 0 || y >= self.height as isize {
+                continue;
+            }
+            for i in 0..other.width {
+                let x = x + i as isize;
+                if x < 0 || x >= self.width as isize {
+                    continue;
+                }
+                let color = other
+                    .get(i, j)
+                    .expect("the pixel is inside the other image");
+                let color = match (other.alpha_mode, self.alpha_mode) {
+                    (AlphaMode::Premultiplied, AlphaMode::Premultiplied) => color,
+                    (_, AlphaMode::Premultiplied) => color.premultiplied(),
+                    (AlphaMode::Premultiplied, _) => color.unpremultiplied(),
+                    _ => color,
+                };
+                self.blend_pixel(x as usize, y as usize, color, mode);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Orientation;
+
+    #[test]
+    fn blend_colors() {
+        let red = Color::rgba(255, 0, 0, 128);
+        let blue = Color::rgb(0, 0, 255);
+        let straight = AlphaMode::Straight;
+
+        assert_eq!(
+            red.blend(blue, BlendMode::Over, straight),
+            Color::rgb(128, 0, 127)
+        );
+        assert_eq!(red.blend(blue, BlendMode::Over, straight), red.over(blue));
+        assert_eq!(
+            blue.blend(red, BlendMode::Over, straight),
+            Color::rgba(0, 0, 255, 255)
+        );
+
+        let clear = Color::rgba(0, 0, 0, 0);
+        assert_eq!(red.blend(clear, BlendMode::Over, straight), red);
+        assert_eq!(red.blend(clear, BlendMode::In, straight), clear);
+        assert_eq!(red.blend(clear, BlendMode::Out, straight), red);
+        assert_eq!(red.blend(clear, BlendMode::Atop, straight), clear);
+        assert_eq!(red.blend(clear, BlendMode::Xor, straight), red);
+
+        let grey = Color::rgb(128, 128, 128);
+        let white = Color::rgb(255, 255, 255);
+        assert_eq!(
+            grey.blend(grey, BlendMode::Multiply, straight),
+            Color::rgb(64, 64, 64)
+        );
+        assert_eq!(
+            grey.blend(grey, BlendMode::Screen, straight),
+            Color::rgb(192, 192, 192)
+        );
+        assert_eq!(white.blend(grey, BlendMode::Overlay, straight), white);
+        assert_eq!(grey.blend(grey, BlendMode::Add, straight), white);
+
+        let premultiplied = red.premultiplied();
+        assert_eq!(premultiplied, Color::rgba(128, 0, 0, 128));
+        assert_eq!(premultiplied.unpremultiplied(), Color::rgba(255, 0, 0, 128));
+        assert_eq!(
+            premultiplied.blend(blue, BlendMode::Over, AlphaMode::Premultiplied),
+            Color::rgb(128, 0, 127)
+        );
+    }
+
+    #[test]
+    fn composite_images() {
+        let mut image = Image::new(4, 3, 3);
+        image.reorient(Orientation::TopLeft);
+        for x in 0..4 {
+            image.set(x, 0, Color::rgb(0, 0, 255));
+        }
+
+        let mut overlay = Image::new(2, 2, 4);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            overlay.set(x, y, Color::rgba(255, 0, 0, 128));
+        }
+        overlay.set(0, 1, Color::rgba(0, 0, 0, 0));
+        image.composite(&overlay, 3, -1, BlendMode::Over);
+
+        assert_eq!(image.get(3, 0), Some(Color::rgb(128, 0, 127)));
+        assert_eq!(image.get(2, 0), Some(Color::rgb(0, 0, 255)));
+        assert_eq!(image.get(3, 1), Some(Color::rgb(0, 0, 0)));
+
+        let mut premultiplied = Image::new(1, 1, 4);
+        premultiplied.set_alpha_mode(AlphaMode::Premultiplied);
+        premultiplied.composite(&overlay, -1, 0, BlendMode::Over);
+        assert_eq!(premultiplied.get(0, 0), Some(Color::rgba(128, 0, 0, 128)));
+        assert!(!premultiplied.blend_pixel(1, 0, Color::rgb(1, 2, 3), BlendMode::Add));
+
+        let mut tile = Image::new(1, 2, 3);
+        tile.reorient(Orientation::TopRight);
+        tile.set(0, 0, Color::rgb(1, 1, 1));
+        tile.set(0, 1, Color::rgb(2, 2, 2));
+        let mut copied = Image::new(2, 3, 3);
+        let mut composited = copied.clone();
+        copied.copy_from(&tile.as_view(), 1, 1);
+        composited.composite(&tile, 1, 1, BlendMode::Over);
+        assert_eq!(composited, copied);
+        assert_eq!(composited.get(1, 1), Some(Color::rgb(2, 2, 2)));
+    }
+}
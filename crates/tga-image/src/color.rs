@@ -0,0 +1,173 @@
+//! Converting colors to and from packed integers, hex strings, HSV and HSL
+
+use std::str::FromStr;
+
+use crate::{Color, TgaError};
+
+impl Color {
+    /// Creates an RGB color from `0xRRGGBB`, the top byte is ignored
+    pub const fn from_rgb_u32(value: u32) -> Self {
+        let [_, r, g, b] = value.to_be_bytes();
+        Self::rgb(r, g, b)
+    }
+
+    /// Creates an RGBA color from `0xRRGGBBAA`
+    pub const fn from_rgba_u32(value: u32) -> Self {
+        let [r, g, b, a] = value.to_be_bytes();
+        Self::rgba(r, g, b, a)
+    }
+
+    /// Packs the color as `0xRRGGBB`, alpha is dropped
+    pub fn to_rgb_u32(self) -> u32 {
+        u32::from_be_bytes([0, self.r(), self.g(), self.b()])
+    }
+
+    /// Packs the color as `0xRRGGBBAA`, a color without alpha is opaque
+    pub fn to_rgba_u32(self) -> u32 {
+        u32::from_be_bytes([self.r(), self.g(), self.b(), self.a()])
+    }
+
+    /// Parses `#rrggbb` as an RGB color or `#rrggbbaa` as an RGBA color, the `#` is optional
+    ///
+    /// ## Errors
+    ///
+    /// - if the text isn't 6 or 8 hex digits [`TgaError::InvalidInput`] is returned
+    pub fn from_hex(hex: &str) -> Result<Self, TgaError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !matches!(digits.len(), 6 | 8) || !digits.bytes().all(|d| d.is_ascii_hexdigit()) {
+            return Err(TgaError::InvalidInput("a hex color is 6 or 8 hex digits"));
+        }
+
+        let value = u32::from_str_radix(digits, 16).expect("the digits are checked");
+        if digits.len() == 6 {
+            Ok(Self::from_rgb_u32(value))
+        } else {
+            Ok(Self::from_rgba_u32(value))
+        }
+    }
+
+    /// Formats the color as `#rrggbbaa` if it has alpha, otherwise `#rrggbb`
+    pub fn to_hex(self) -> String {
+        match self.bytes_pp {
+            4 => format!("#{:08x}", self.to_rgba_u32()),
+            _ => format!("#{:06x}", self.to_rgb_u32()),
+        }
+    }
+
+    /// The hue in degrees from 0 to 360, and the saturation and value from 0 to 1, alpha is
+    /// ignored
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (hue, saturation, max)
+    }
+
+    /// The hue in degrees from 0 to 360, and the saturation and lightness from 0 to 1, alpha is
+    /// ignored
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        (hue, saturation, lightness)
+    }
+
+    /// Creates an RGB color from a hue in degrees, and a saturation and value from 0 to 1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let value = value.clamp(0.0, 1.0);
+        let chroma = value * saturation.clamp(0.0, 1.0);
+        Self::from_hue(hue, chroma, value - chroma)
+    }
+
+    /// Creates an RGB color from a hue in degrees, and a saturation and lightness from 0 to 1
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let lightness = lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation.clamp(0.0, 1.0);
+        Self::from_hue(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    /// The hue in degrees and the largest and smallest of red, green and blue from 0 to 1
+    fn hue(self) -> (f32, f32, f32) {
+        let [r, g, b] = [self.r(), self.g(), self.b()].map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (hue, max, min)
+    }
+
+    /// Creates an RGB color from a hue in degrees, the chroma and the amount added to each
+    /// channel
+    fn from_hue(hue: f32, chroma: f32, offset: f32) -> Self {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let channel = |c: f32| ((c + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Self::rgb(channel(r), channel(g), channel(b))
+    }
+}
+
+impl FromStr for Color {
+    type Err = TgaError;
+
+    /// See [`Color::from_hex`]
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(hex)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn color_conversions() {
+        let orange = Color::rgba(255, 128, 0, 64);
+        assert_eq!(
+            (orange.r(), orange.g(), orange.b(), orange.a()),
+            (255, 128, 0, 64)
+        );
+        assert_eq!(Color::grey_scale(7).g(), 7);
+        assert_eq!(Color::rgb(1, 2, 3).a(), 255);
+
+        assert_eq!(orange.to_rgba_u32(), 0xff80_0040);
+        assert_eq!(Color::from_rgb_u32(0x12ff8000), Color::rgb(255, 128, 0));
+        assert_eq!(orange.to_hex(), "#ff800040");
+        assert_eq!(Color::grey_scale(0xab).to_hex(), "#ababab");
+        assert_eq!("#ff800040".parse::<Color>().unwrap(), orange);
+        assert_eq!(Color::from_hex("FF8000").unwrap(), Color::rgb(255, 128, 0));
+        assert!(Color::from_hex("#ff80").is_err());
+        assert!(Color::from_hex("+ff800").is_err());
+
+        let (h, s, v) = orange.to_hsv();
+        assert!((h - 30.1).abs() < 0.1 && s == 1.0 && v == 1.0);
+        assert_eq!(Color::from_hsv(h, s, v), Color::rgb(255, 128, 0));
+        let (h, s, l) = Color::rgb(64, 128, 192).to_hsl();
+        assert!((h - 210.0).abs() < 0.1 && (s - 0.5).abs() < 0.01 && (l - 0.502).abs() < 0.01);
+        assert_eq!(Color::from_hsl(h, s, l), Color::rgb(64, 128, 192));
+        assert_eq!(Color::from_hsl(0.0, 0.0, 1.0), Color::rgb(255, 255, 255));
+
+        // only the channels of the format are compared
+        assert_eq!(Color::rgb(1, 2, 3), Color::rgba(1, 2, 3, 0).to_rgb());
+        assert_ne!(Color::rgb(9, 9, 9), Color::grey_scale(9));
+    }
+}
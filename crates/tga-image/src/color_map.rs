@@ -0,0 +1,532 @@
+//! Color mapped (palette) images, these are the data type codes 1 and 9
+
+use std::{fs, io, path::Path};
+
+use crate::{Color, DecodeLimits, Encoding, Image, Orientation, TgaError, TgaFile, TgaHeader};
+
+/// # A table of colors referenced by index
+///
+/// The first entry of the table is addressed by `first_index` rather than 0, indices below it
+/// or past the end of the table don't refer to a color.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ColorMap {
+    first_index: u16,
+    entries: Vec<Color>,
+}
+
+impl ColorMap {
+    /// Creates a new color map
+    ///
+    /// ## returns
+    ///
+    /// - None if there are no entries, they are not all RGB or all RGBA or there are too many to
+    ///   index
+    /// - Some(ColorMap) otherwise
+    pub fn new(first_index: u16, entries: Vec<Color>) -> Option<Self> {
+        let bytes_pp = entries.first()?.bytes_pp;
+        if bytes_pp < 3 || entries.iter().any(|color| color.bytes_pp != bytes_pp) {
+            return None;
+        }
+        if first_index as usize + entries.len() > u16::MAX as usize + 1 {
+            return None;
+        }
+
+        Some(Self {
+            first_index,
+            entries,
+        })
+    }
+
+    pub(crate) fn from_reader<R>(
+        mut reader: R,
+        header: &TgaHeader,
+    ) -> Result<Option<Self>, TgaError>
+    where
+        R: io::Read,
+    {
+        if header.color_map_type == 0 {
+            return Ok(None);
+        }
+
+        let entry_size = (header.color_map_depth as usize).div_ceil(8);
+        let mut buf = vec![0u8; entry_size * header.color_map_length as usize];
+        reader.read_exact(&mut buf)?;
+
+        let alpha = header.color_map_depth == 16 && header.alpha_bits() != 0;
+        let entries = buf
+            .chunks_exact(entry_size)
+            .map(|entry| match *entry {
+                [lo, hi] => Color::from_a1r5g5b5(u16::from_le_bytes([lo, hi]), alpha),
+                [b, g, r] => Color::rgb(r, g, b),
+                [b, g, r, a] => Color::rgba(r, g, b, a),
+                _ => unreachable!("color map depth is validated with the header"),
+            })
+            .collect();
+
+        Ok(Some(Self {
+            first_index: header.color_map_origin,
+            entries,
+        }))
+    }
+
+    /// Looks up the color for an index
+    ///
+    /// ## returns
+    ///
+    /// - Some(Color) if the index refers to an entry in this color map
+    /// - None otherwise
+    pub fn get(&self, index: u16) -> Option<Color> {
+        let offset = index.checked_sub(self.first_index)?;
+        self.entries.get(offset as usize).copied()
+    }
+
+    /// The index of the first entry
+    pub fn first_index(&self) -> u16 {
+        self.first_index
+    }
+
+    /// All of the colors in this color map, in index order
+    pub fn entries(&self) -> &[Color] {
+        &self.entries
+    }
+
+    /// The number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Getter for the pixel format of the entries represented as bytes per pixel
+    pub fn format(&self) -> usize {
+        self.entries.first().map_or(3, |color| color.bytes_pp)
+    }
+}
+
+/// # An image that stores an index into a color map for each pixel
+///
+/// This keeps the palette of a color mapped TGA rather than expanding it, use
+/// [`IndexedImage::to_image`] to get the true color image.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IndexedImage {
+    width: usize,
+    height: usize,
+    indices: Vec<u16>,
+    color_map: ColorMap,
+    id: Vec<u8>,
+    orientation: Orientation,
+}
+
+impl IndexedImage {
+    /// Creates a new image with every pixel set to the first entry of the color map
+    pub fn new(width: usize, height: usize, color_map: ColorMap) -> Self {
+        Self {
+            width,
+            height,
+            indices: vec![color_map.first_index; width * height],
+            color_map,
+            id: Vec::new(),
+            orientation: Orientation::BottomLeft,
+        }
+    }
+
+    pub(crate) fn from_raw(
+        width: usize,
+        height: usize,
+        indices: Vec<u16>,
+        color_map: ColorMap,
+        orientation: Orientation,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            indices,
+            color_map,
+            id: Vec::new(),
+            orientation,
+        }
+    }
+
+    /// # Reads a color mapped image from a file
+    ///
+    /// ## errors
+    ///
+    /// - while opening the file see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
+    /// - while reading the file see [`IndexedImage::from_reader`]
+    pub fn read_tga_file<P>(filename: P) -> Result<Self, TgaError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = std::fs::File::open(filename.as_ref())?;
+
+        Self::from_reader(file)
+    }
+
+    /// # Reads a color mapped image from a buffer in memory
+    ///
+    /// ## errors
+    ///
+    /// - see [`IndexedImage::from_reader`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TgaError> {
+        Self::from_reader(bytes)
+    }
+
+    /// Reads a color mapped image from an `io::Read`, it is reoriented like [`Image::from_reader`]
+    ///
+    /// ## Errors
+    ///
+    /// - see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
+    /// - if the image isn't color mapped [`TgaError::UnsupportedDataType`] is returned
+    /// - otherwise see [`Image::from_reader`]
+    pub fn from_reader<R>(reader: R) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        Self::from_reader_with_limits(reader, DecodeLimits::new())
+    }
+
+    /// Reads a color mapped image from an `io::Read`, checking the size of the image against
+    /// `limits`
+    ///
+    /// ## Errors
+    ///
+    /// - if the image is larger than the limits [`TgaError::LimitExceeded`] is returned
+    /// - otherwise see [`IndexedImage::from_reader`]
+    pub fn from_reader_with_limits<R>(mut reader: R, limits: DecodeLimits) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        let header = TgaHeader::from_reader(&mut reader)?;
+        limits.check(&header)?;
+        let id = header.read_image_id(&mut reader)?;
+        let color_map = ColorMap::from_reader(&mut reader, &header)?;
+
+        match color_map {
+            Some(color_map) if header.is_color_mapped() => {
+                let mut image = Self::from_parts(reader, &header, id, color_map)?;
+                image.reorient(Orientation::TopLeft);
+                Ok(image)
+            }
+            _ => Err(TgaError::UnsupportedDataType(header.data_type_code)),
+        }
+    }
+
+    /// Reads the pixel indices that follow the color map, in the order they are stored
+    pub(crate) fn from_parts<R>(
+        reader: R,
+        header: &TgaHeader,
+        id: Vec<u8>,
+        color_map: ColorMap,
+    ) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        let data = header.read_pixels(reader)?;
+        let image = Self::from_data(header, id, color_map, &data);
+
+        if image
+            .indices
+            .iter()
+            .any(|&index| image.color_map.get(index).is_none())
+        {
+            return Err(TgaError::BadColorMap("color map index out of range"));
+        }
+        Ok(image)
+    }
+
+    /// Builds the image from the indices as they are stored, without checking they are in the
+    /// color map
+    pub(crate) fn from_data(
+        header: &TgaHeader,
+        id: Vec<u8>,
+        color_map: ColorMap,
+        data: &[u8],
+    ) -> Self {
+        let bytes_pp = header.bytes_pp();
+        let indices = data
+            .chunks_exact(bytes_pp)
+            .map(|index| match *index {
+                [i] => i as u16,
+                [lo, hi] => u16::from_le_bytes([lo, hi]),
+                _ => unreachable!("index size is validated with the header"),
+            })
+            .collect();
+
+        Self {
+            width: header.width as usize,
+            height: header.height as usize,
+            indices,
+            color_map,
+            id,
+            orientation: Orientation::from_descriptor(header.image_descriptor),
+        }
+    }
+
+    /// # Writes a color mapped image to a file
+    ///
+    /// Indices are written with 8 bits if every entry of the color map can be addressed with
+    /// them, otherwise 16 bits are used.
+    ///
+    /// ## errors
+    ///
+    /// - while creating the file see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
+    pub fn write_tga_file<P, E>(&self, filename: P, encoding: E) -> Result<(), TgaError>
+    where
+        P: AsRef<Path>,
+        E: Into<Option<Encoding>>,
+    {
+        let file = fs::File::create(filename.as_ref())?;
+        self.write_to(io::BufWriter::new(file), encoding)
+    }
+
+    /// # Writes a color mapped image to an `io::Write`, see [`IndexedImage::write_tga_file`]
+    ///
+    /// The output isn't buffered, wrap it in an `io::BufWriter` if needed
+    ///
+    /// ## errors
+    ///
+    /// - any error from writing to `output`
+    /// - if the width or height is over 32767 [`TgaError::InvalidInput`] is returned
+    pub fn write_to<W, E>(&self, output: W, encoding: E) -> Result<(), TgaError>
+    where
+        W: io::Write,
+        E: Into<Option<Encoding>>,
+    {
+        let encoding = encoding.into();
+
+        let data_type_code = match encoding {
+            Some(_) => 9,
+            None => 1,
+        };
+
+        let wide = self.color_map.first_index as usize + self.color_map.len() > 256;
+        let data = if wide {
+            self.indices.iter().flat_map(|i| i.to_le_bytes()).collect()
+        } else {
+            self.indices.iter().map(|&i| i as u8).collect::<Vec<_>>()
+        };
+
+        let color_map = self
+            .color_map
+            .entries
+            .iter()
+            .flat_map(|color| color.as_slice().iter().copied())
+            .collect::<Vec<_>>();
+
+        let alpha_bits = if self.color_map.format() == 4 { 8 } else { 0 };
+        let header = TgaHeader {
+            color_map_type: 1,
+            data_type_code,
+            color_map_origin: self.color_map.first_index,
+            color_map_length: self.color_map.len() as u16,
+            color_map_depth: self.color_map.format() as u8 * 8,
+            width: TgaHeader::dimension(self.width)?,
+            height: TgaHeader::dimension(self.height)?,
+            bits_per_pixel: if wide { 16 } else { 8 },
+            image_descriptor: alpha_bits | self.orientation.descriptor_bits(),
+            ..Default::default()
+        };
+
+        let tga = TgaFile {
+            header,
+            id: &self.id,
+            color_map: &color_map,
+            data: &data,
+            encoding: &encoding,
+            metadata: None,
+            attributes_type: None,
+            developer_area: None,
+            postage_stamp: None,
+        };
+
+        tga.write(output)
+    }
+
+    /// # Writes a color mapped image to a buffer in memory
+    ///
+    /// ## errors
+    ///
+    /// - see [`IndexedImage::write_to`]
+    pub fn to_bytes<E>(&self, encoding: E) -> Result<Vec<u8>, TgaError>
+    where
+        E: Into<Option<Encoding>>,
+    {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes, encoding)?;
+        Ok(bytes)
+    }
+
+    /// Expands the indices into their colors
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height, self.color_map.format());
+        let bytes_pp = image.bytes_pp;
+        for (pixel, &index) in image.data.chunks_exact_mut(bytes_pp).zip(&self.indices) {
+            if let Some(color) = self.color_map.get(index) {
+                pixel.copy_from_slice(color.as_slice());
+            }
+        }
+        image.id = self.id.clone();
+        image.orientation = self.orientation;
+        image
+    }
+
+    /// The order the pixels are stored in, see [`Image::orientation`]
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Flips the image as needed so the pixels are stored in `orientation`, see
+    /// [`Image::reorient`]
+    pub fn reorient(&mut self, orientation: Orientation) {
+        if self.orientation.is_right() != orientation.is_right() {
+            self.flip_horizontally();
+        }
+        if self.orientation.is_top() != orientation.is_top() {
+            self.flip_vertically();
+        }
+        self.orientation = orientation;
+    }
+
+    /// Turns the image into its mirror image along the horizontal axis,
+    /// this occurs in place
+    pub fn flip_horizontally(&mut self) {
+        if self.width == 0 {
+            return;
+        }
+        for row in self.indices.chunks_exact_mut(self.width) {
+            row.reverse();
+        }
+    }
+
+    /// Turns the image into its mirror image along the vertical axis.
+    pub fn flip_vertically(&mut self) {
+        let width = self.width;
+        for j in 0..self.height / 2 {
+            let (top, bottom) = self.indices.split_at_mut((self.height - 1 - j) * width);
+            top[j * width..][..width].swap_with_slice(&mut bottom[..width]);
+        }
+    }
+
+    /// Access the index of a single pixel within the image
+    ///
+    /// ## returns
+    ///
+    /// - Some(u16) if the indicies are inside the image area
+    /// - None otherwise
+    pub fn index(&self, x: usize, y: usize) -> Option<u16> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.indices[x + y * self.width])
+    }
+
+    /// Sets the index of a single pixel
+    ///
+    /// ## returns
+    ///
+    /// - true if the index is in the color map and the pixel is inside the image
+    /// - false otherwise
+    pub fn set_index(&mut self, x: usize, y: usize, index: u16) -> bool {
+        if x >= self.width || y >= self.height || self.color_map.get(index).is_none() {
+            return false;
+        }
+        self.indices[x + y * self.width] = index;
+        true
+    }
+
+    /// Access the color of a single pixel within the image
+    ///
+    /// ## returns
+    ///
+    /// - Some(Color) if the indicies are inside the image area
+    /// - None otherwise
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        self.index(x, y).and_then(|index| self.color_map.get(index))
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The index of every pixel, row by row
+    pub fn indices(&self) -> &[u16] {
+        &self.indices
+    }
+
+    /// The image ID field, see [`Image::id`]
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Sets the image ID field, see [`Image::set_id`]
+    pub fn set_id<I>(&mut self, id: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let id = id.as_ref();
+        if id.len() > u8::MAX as usize {
+            return false;
+        }
+        self.id = id.to_vec();
+        true
+    }
+
+    /// Getter for the color map
+    pub fn color_map(&self) -> &ColorMap {
+        &self.color_map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Image;
+
+    #[test]
+    fn rle_color_mapped() {
+        #[rustfmt::skip]
+        let file = [
+            // header: 2 entries of 24 bits starting at index 4, 8 bit indices, 3x2 top left
+            0, 1, 9, 4, 0, 2, 0, 24, 0, 0, 0, 0, 3, 0, 2, 0, 8, 0x20,
+            // color map: red, green
+            0, 0, 255, 0, 255, 0,
+            // a run of 4 reds then 2 raw pixels
+            0x83, 4, 0x01, 5, 4,
+        ];
+
+        let indexed = IndexedImage::from_reader(&file[..]).unwrap();
+        assert_eq!(indexed.indices(), &[4, 4, 4, 4, 5, 4]);
+        assert_eq!(indexed.get(1, 1), Some(Color::rgb(0, 255, 0)));
+
+        let image = Image::from_reader(&file[..]).unwrap();
+        assert_eq!(image, indexed.to_image());
+        assert_eq!(image.get(2, 1), Some(Color::rgb(255, 0, 0)));
+
+        assert!(ColorMap::new(0, Vec::new()).is_none());
+    }
+
+    #[test]
+    fn write_read_color_mapped() {
+        let entries = vec![Color::rgba(255, 0, 0, 255), Color::rgba(0, 0, 255, 128)];
+        let mut image = IndexedImage::new(4, 3, ColorMap::new(10, entries).unwrap());
+        image.set_index(1, 0, 11);
+        image.set_index(3, 2, 11);
+
+        let path = std::env::temp_dir().join("tga_image_write_read_color_mapped.tga");
+        image.write_tga_file(&path, Encoding::Rle).unwrap();
+        let mut read = IndexedImage::read_tga_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        read.reorient(Orientation::BottomLeft);
+        assert_eq!(read, image);
+    }
+}
@@ -0,0 +1,265 @@
+//! Converting pixels between grey scale, RGB and RGBA
+
+use crate::{AlphaMode, Color, Image};
+
+/// The number and meaning of the bytes of each pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// - Grey: 1 byte of brightness
+    Grey,
+    /// - Rgb: 3 bytes, stored as blue, green, red
+    Rgb,
+    /// - Rgba: 4 bytes, stored as blue, green, red, alpha
+    Rgba,
+}
+
+impl PixelFormat {
+    /// The format with `bytes_pp` bytes per pixel, None unless it is 1, 3 or 4
+    pub fn from_bytes_pp(bytes_pp: usize) -> Option<Self> {
+        match bytes_pp {
+            1 => Some(Self::Grey),
+            3 => Some(Self::Rgb),
+            4 => Some(Self::Rgba),
+            _ => None,
+        }
+    }
+
+    /// The number of bytes per pixel
+    pub fn bytes_pp(self) -> usize {
+        match self {
+            Self::Grey => 1,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+/// How much each of red, green and blue adds to the brightness of a grey scale pixel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LumaWeights {
+    /// - Bt601: 0.299 red, 0.587 green and 0.114 blue, for standard definition video and most
+    ///   images
+    #[default]
+    Bt601,
+    /// - Bt709: 0.2126 red, 0.7152 green and 0.0722 blue, for HD video and sRGB
+    Bt709,
+}
+
+impl LumaWeights {
+    /// The weights of red, green and blue out of 10000
+    fn weights(self) -> [u32; 3] {
+        match self {
+            Self::Bt601 => [2990, 5870, 1140],
+            Self::Bt709 => [2126, 7152, 722],
+        }
+    }
+}
+
+/// # Options for converting between pixel formats
+///
+/// By default grey scale uses [`LumaWeights::Bt601`], added alpha is opaque and dropped alpha
+/// is ignored.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    weights: LumaWeights,
+    alpha: u8,
+    background: Option<Color>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            weights: LumaWeights::default(),
+            alpha: 255,
+            background: None,
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// Creates the default options, see [`ConvertOptions`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the weights used to convert to grey scale
+    pub fn weights(mut self, weights: LumaWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Sets the alpha given to pixels converted to RGBA
+    pub fn alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Sets the color pixels are composited over when their alpha is dropped, `None` drops the
+    /// alpha without compositing
+    pub fn background<C>(mut self, background: C) -> Self
+    where
+        C: Into<Option<Color>>,
+    {
+        self.background = background.into();
+        self
+    }
+}
+
+impl Color {
+    /// The brightness of the color, an average of red, green and blue using `weights`, alpha is
+    /// ignored
+    pub fn luminance(self, weights: LumaWeights) -> u8 {
+        if self.bytes_pp == 1 {
+            return self.bgra[0];
+        }
+        let [b, g, r, _] = self.bgra;
+        let [wr, wg, wb] = weights.weights();
+        ((r as u32 * wr + g as u32 * wg + b as u32 * wb + 5000) / 10000) as u8
+    }
+
+    /// Converts to grey scale using `weights`, alpha is dropped
+    pub fn to_grey_scale(self, weights: LumaWeights) -> Self {
+        Self::grey_scale(self.luminance(weights))
+    }
+
+    /// Converts to RGB, grey scale is spread to each channel and alpha is dropped
+    pub fn to_rgb(self) -> Self {
+        match self.bytes_pp {
+            1 => Self::rgb(self.bgra[0], self.bgra[0], self.bgra[0]),
+            _ => Self::rgb(self.bgra[2], self.bgra[1], self.bgra[0]),
+        }
+    }
+
+    /// Converts to RGBA, `alpha` is used if the color doesn't have any
+    pub fn to_rgba(self, alpha: u8) -> Self {
+        match self.bytes_pp {
+            4 => self,
+            _ => {
+                let [b, g, r, _] = self.to_rgb().bgra;
+                Self::rgba(r, g, b, alpha)
+            }
+        }
+    }
+
+    /// Composites the color over an opaque `background` using its alpha
+    ///
+    /// ## returns
+    ///
+    /// - an RGB color, a color without alpha is just converted to RGB
+    pub fn over(self, background: Color) -> Self {
+        if self.bytes_pp != 4 {
+            return self.to_rgb();
+        }
+        let alpha = self.bgra[3] as u32;
+        let [b, g, r, _] = background.to_rgb().bgra;
+        let mix =
+            |c: u8, bg: u8| ((c as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+        Self::rgb(
+            mix(self.bgra[2], r),
+            mix(self.bgra[1], g),
+            mix(self.bgra[0], b),
+        )
+    }
+
+    /// Converts to `format` as described by `options`
+    pub fn convert(self, format: PixelFormat, options: &ConvertOptions) -> Self {
+        let color = match options.background {
+            Some(background) if format != PixelFormat::Rgba => self.over(background),
+            _ => self,
+        };
+        match format {
+            PixelFormat::Grey => color.to_grey_scale(options.weights),
+            PixelFormat::Rgb => color.to_rgb(),
+            PixelFormat::Rgba => color.to_rgba(options.alpha),
+        }
+    }
+}
+
+impl Image {
+    /// The format of the pixels, None if the image was created with an unsupported number of
+    /// bytes per pixel
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        PixelFormat::from_bytes_pp(self.bytes_pp)
+    }
+
+    /// Converts the pixels to `format` with the default options, see [`ConvertOptions`]
+    pub fn convert(&self, format: PixelFormat) -> Image {
+        self.convert_with_options(format, &ConvertOptions::new())
+    }
+
+    /// Converts the pixels to `format` as described by `options`, everything else about the
+    /// image is kept
+    ///
+    /// Premultiplied pixels are unpremultiplied before their alpha is dropped, the converted
+    /// image then has straight alpha.
+    pub fn convert_with_options(&self, format: PixelFormat, options: &ConvertOptions) -> Image {
+        let bytes_pp = format.bytes_pp();
+        let unpremultiply =
+            self.alpha_mode == AlphaMode::Premultiplied && format != PixelFormat::Rgba;
+        let mut data = Vec::with_capacity(self.width * self.height * bytes_pp);
+        for pixel in self.data.chunks_exact(self.bytes_pp) {
+            let mut color = Color::try_from_slice(pixel).unwrap_or_default();
+            if unpremultiply {
+                color = color.unpremultiplied();
+            }
+            data.extend_from_slice(color.convert(format, options).as_slice());
+        }
+
+        Image {
+            bytes_pp,
+            data,
+            id: self.id.clone(),
+            metadata: self.metadata.clone(),
+            developer_area: self.developer_area.clone(),
+            alpha_mode: if unpremultiply {
+                AlphaMode::Straight
+            } else {
+                self.alpha_mode
+            },
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_formats() {
+        let orange = Color::rgba(255, 128, 0, 128);
+        assert_eq!(orange.luminance(LumaWeights::Bt601), 151);
+        assert_eq!(orange.luminance(LumaWeights::Bt709), 146);
+        assert_eq!(Color::rgb(255, 255, 255).luminance(LumaWeights::Bt709), 255);
+        assert_eq!(orange.to_rgb(), Color::rgb(255, 128, 0));
+        assert_eq!(Color::grey_scale(9).to_rgba(7), Color::rgba(9, 9, 9, 7));
+        assert_eq!(orange.over(Color::rgb(0, 0, 255)), Color::rgb(128, 64, 127));
+
+        let mut image = Image::new(2, 1, 4);
+        image.set(0, 0, orange);
+        image.set_id("converted");
+
+        let grey = image.convert(PixelFormat::Grey);
+        assert_eq!(grey.pixel_format(), Some(PixelFormat::Grey));
+        assert_eq!(grey.get(0, 0), Some(Color::grey_scale(151)));
+        assert_eq!(grey.id(), b"converted");
+
+        let options_over_blue = || ConvertOptions::new().background(Color::rgb(0, 0, 255));
+        let rgb = image.convert_with_options(PixelFormat::Rgb, &options_over_blue());
+        assert_eq!(rgb.get(0, 0), Some(Color::rgb(128, 64, 127)));
+        assert_eq!(rgb.get(1, 0), Some(Color::rgb(0, 0, 255)));
+
+        let options = ConvertOptions::new().alpha(200);
+        let rgba = grey.convert_with_options(PixelFormat::Rgba, &options);
+        assert_eq!(rgba.get(0, 0), Some(Color::rgba(151, 151, 151, 200)));
+
+        image.premultiply();
+        let rgb = image.convert_with_options(PixelFormat::Rgb, &options_over_blue());
+        assert_eq!(rgb.alpha_mode(), AlphaMode::Straight);
+        assert_eq!(rgb.get(0, 0), Some(Color::rgb(128, 64, 127)));
+        assert_eq!(
+            image.convert(PixelFormat::Rgba).alpha_mode(),
+            AlphaMode::Premultiplied
+        );
+    }
+}
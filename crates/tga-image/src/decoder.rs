@@ -0,0 +1,333 @@
+//! Reading an image one row at a time
+
+use std::io;
+
+use crate::{Color, ColorMap, DecodeLimits, Orientation, TgaError, TgaHeader};
+
+/// The run length packet being read, packets may continue from one row to the next
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RlePacket {
+    remaining: usize,
+    run: bool,
+    pixel: [u8; 4],
+}
+
+impl RlePacket {
+    /// Fills `data` with pixels, continuing the current packet before reading new ones
+    pub(crate) fn read<R>(
+        &mut self,
+        reader: R,
+        bytes_pp: usize,
+        data: &mut [u8],
+    ) -> Result<(), TgaError>
+    where
+        R: io::Read,
+    {
+        self.read_counted(reader, bytes_pp, data, &mut 0)
+    }
+
+    /// Fills `data` like [`RlePacket::read`], `filled` is the number of bytes of `data` that
+    /// were filled even if reading fails part way
+    pub(crate) fn read_counted<R>(
+        &mut self,
+        mut reader: R,
+        bytes_pp: usize,
+        data: &mut [u8],
+        filled: &mut usize,
+    ) -> Result<(), TgaError>
+    where
+        R: io::Read,
+    {
+        while *filled < data.len() {
+            if self.remaining == 0 {
+                let mut header = 0;
+                reader.read_exact(std::slice::from_mut(&mut header))?;
+                self.run = header >= 128;
+                self.remaining = (header & 0x7f) as usize + 1;
+                if self.run {
+                    reader.read_exact(&mut self.pixel[..bytes_pp])?;
+                }
+            }
+
+            let n = self.remaining.min((data.len() - *filled) / bytes_pp);
+            let pixels = &mut data[*filled..*filled + n * bytes_pp];
+            if self.run {
+                for pixel in pixels.chunks_exact_mut(bytes_pp) {
+                    pixel.copy_from_slice(&self.pixel[..bytes_pp]);
+                }
+            } else {
+                reader.read_exact(pixels)?;
+            }
+            self.remaining -= n;
+            *filled += n * bytes_pp;
+        }
+        Ok(())
+    }
+
+    /// Checks that the last packet ended with the image
+    pub(crate) fn finish(&self) -> Result<(), TgaError> {
+        if self.remaining != 0 {
+            return Err(TgaError::RleOverrun);
+        }
+        Ok(())
+    }
+}
+
+/// # Reads an image one row at a time
+///
+/// The header, image ID and color map are read up front, then each call to
+/// [`TgaDecoder::read_row`] decodes a single row so only one row of the image is held in memory.
+/// Rows are decoded to the same formats as [`Image`](crate::Image), expanding color maps and 15
+/// and 16 bit pixels.
+///
+/// Rows are given in the order they are stored, see [`TgaDecoder::orientation`]. Reading is done
+/// in small pieces so the reader should be buffered.
+#[derive(Debug)]
+pub struct TgaDecoder<R> {
+    reader: R,
+    header: TgaHeader,
+    id: Vec<u8>,
+    color_map: Option<ColorMap>,
+    packet: RlePacket,
+    stored: Vec<u8>,
+    row: Vec<u8>,
+    rows_read: usize,
+}
+
+impl<R: io::Read> TgaDecoder<R> {
+    /// Reads everything before the pixels
+    ///
+    /// ## Errors
+    ///
+    /// - any error from reading
+    /// - if the format isn't supported the matching [`TgaError`] is returned
+    pub fn new(reader: R) -> Result<Self, TgaError> {
+        Self::with_limits(reader, DecodeLimits::new())
+    }
+
+    /// Reads everything before the pixels, checking the size of the image against `limits`
+    ///
+    /// ## Errors
+    ///
+    /// - see [`TgaDecoder::new`]
+    /// - if the image is too large [`TgaError::LimitExceeded`] is returned
+    pub fn with_limits(mut reader: R, limits: DecodeLimits) -> Result<Self, TgaError> {
+        let header = TgaHeader::from_reader(&mut reader)?;
+        limits.check(&header)?;
+        let id = header.read_image_id(&mut reader)?;
+        let color_map = ColorMap::from_reader(&mut reader, &header)?;
+
+        if !matches!(header.data_type_code, 1 | 2 | 3 | 9 | 10 | 11) {
+            return Err(TgaError::UnsupportedDataType(header.data_type_code));
+        }
+
+        let width = header.width as usize;
+        let mut decoder = Self {
+            reader,
+            header,
+            id,
+            color_map,
+            packet: RlePacket::default(),
+            stored: vec![0u8; width * header.bytes_pp()],
+            row: Vec::new(),
+            rows_read: 0,
+        };
+        decoder.row = vec![0u8; width * decoder.format()];
+        Ok(decoder)
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        self.header.width as usize
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        self.header.height as usize
+    }
+
+    /// The number of bytes per pixel of the decoded rows
+    pub fn format(&self) -> usize {
+        match (&self.color_map, self.header.bytes_pp()) {
+            (Some(color_map), _) if self.header.is_color_mapped() => color_map.format(),
+            (_, 2) if self.has_alpha() => 4,
+            (_, 2) => 3,
+            (_, bytes_pp) => bytes_pp,
+        }
+    }
+
+    /// The order the rows are stored in, the first row read is the top row if this is
+    /// [`Orientation::TopLeft`] or [`Orientation::TopRight`]
+    pub fn orientation(&self) -> Orientation {
+        Orientation::from_descriptor(self.header.image_descriptor)
+    }
+
+    /// The position on screen of the image's lower left corner, see
+    /// [`Image::origin`](crate::Image::origin)
+    pub fn origin(&self) -> (u16, u16) {
+        (self.header.x_origin as u16, self.header.y_origin as u16)
+    }
+
+    /// The image ID field, see [`Image::id`](crate::Image::id)
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// The color map if the image is color mapped
+    pub fn color_map(&self) -> Option<&ColorMap> {
+        self.color_map
+            .as_ref()
+            .filter(|_| self.header.is_color_mapped())
+    }
+
+    /// The number of rows left to read
+    pub fn rows_remaining(&self) -> usize {
+        self.height() - self.rows_read
+    }
+
+    /// Decodes the next row
+    ///
+    /// ## returns
+    ///
+    /// - Some(row) with the bytes of each pixel in the row
+    /// - None once every row has been read
+    ///
+    /// ## Errors
+    ///
+    /// - any error from reading
+    /// - if a color map index is out of range [`TgaError::BadColorMap`] is returned
+    pub fn read_row(&mut self) -> Result<Option<&[u8]>, TgaError> {
+        if self.rows_read == self.height() {
+            return Ok(None);
+        }
+
+        let bytes_pp = self.header.bytes_pp();
+        if self.header.is_rle() {
+            self.packet
+                .read(&mut self.reader, bytes_pp, &mut self.stored)?;
+        } else {
+            self.reader.read_exact(&mut self.stored)?;
+        }
+
+        let format = self.format();
+        let alpha = self.has_alpha();
+        let color_mapped = self.header.is_color_mapped();
+        let pixels = self.stored.chunks_exact(bytes_pp);
+        let decoded = self.row.chunks_exact_mut(format);
+        match self.color_map.as_ref().filter(|_| color_mapped) {
+            Some(color_map) => {
+                for (index, pixel) in pixels.zip(decoded) {
+                    let index = match *index {
+                        [i] => i as u16,
+                        [lo, hi] => u16::from_le_bytes([lo, hi]),
+                        _ => unreachable!("index size is validated with the header"),
+                    };
+                    let color = color_map
+                        .get(index)
+                        .ok_or(TgaError::BadColorMap("color map index out of range"))?;
+                    pixel.copy_from_slice(color.as_slice());
+                }
+            }
+            None if bytes_pp == 2 => {
+                for (value, pixel) in pixels.zip(decoded) {
+                    let value = u16::from_le_bytes([value[0], value[1]]);
+                    pixel.copy_from_slice(Color::from_a1r5g5b5(value, alpha).as_slice());
+                }
+            }
+            None => self.row.copy_from_slice(&self.stored),
+        }
+
+        self.rows_read += 1;
+        if self.rows_read == self.height() {
+            self.packet.finish()?;
+        }
+        Ok(Some(&self.row))
+    }
+
+    /// Turns the decoder into an iterator over the remaining rows, it stops after the first error
+    pub fn into_rows(self) -> Rows<R> {
+        Rows {
+            decoder: self,
+            failed: false,
+        }
+    }
+
+    fn has_alpha(&self) -> bool {
+        self.header.bits_per_pixel == 16 && self.header.alpha_bits() != 0
+    }
+}
+
+/// An iterator over the rows of a [`TgaDecoder`], see [`TgaDecoder::into_rows`]
+#[derive(Debug)]
+pub struct Rows<R> {
+    decoder: TgaDecoder<R>,
+    failed: bool,
+}
+
+impl<R: io::Read> Iterator for Rows<R> {
+    type Item = Result<Vec<u8>, TgaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.decoder.read_row() {
+            Ok(row) => row.map(|row| Ok(row.to_vec())),
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if self.failed {
+            0
+        } else {
+            self.decoder.rows_remaining()
+        };
+        (0, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Encoding, Image, ReadOptions, WriteOptions};
+
+    #[test]
+    fn decode_rows() {
+        let mut image = Image::new(5, 3, 4);
+        for x in 0..5 {
+            image.set(x, 1, Color::rgba(200, 100, 50, 255));
+        }
+        image.set(2, 2, Color::rgba(0, 0, 255, 255));
+
+        let kept = ReadOptions::new().orientation(None);
+        for options in [
+            WriteOptions::new(),
+            WriteOptions::new().encoding(Encoding::Rle),
+            WriteOptions::new()
+                .encoding(Encoding::Rle)
+                .bits_per_pixel(16),
+        ]
+        .iter()
+        {
+            let bytes = image.to_bytes(options.clone()).unwrap();
+            let read = Image::from_reader_with_options(&bytes[..], &kept).unwrap();
+
+            let decoder = TgaDecoder::new(&bytes[..]).unwrap();
+            assert_eq!((decoder.width(), decoder.height()), (5, 3));
+            assert_eq!(decoder.orientation(), Orientation::BottomLeft);
+            let rows = decoder.into_rows().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(rows.concat(), read.as_slice());
+        }
+
+        let indexed = image.quantize(crate::Quantizer::Octree, crate::Dither::None);
+        let bytes = indexed.to_bytes(Encoding::Rle).unwrap();
+        let mut decoder = TgaDecoder::new(&bytes[..]).unwrap();
+        assert_eq!(decoder.color_map(), Some(indexed.color_map()));
+        let row = decoder.read_row().unwrap().unwrap();
+        assert_eq!(&row[8..12], Color::rgba(0, 0, 0, 0).as_slice());
+    }
+}
@@ -0,0 +1,223 @@
+//! The TGA 2.0 developer area
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::{self, Read, SeekFrom},
+};
+
+use crate::{DecodeLimits, TgaError};
+
+/// # Tagged fields an application stores in a TGA 2.0 file
+///
+/// Each field is a blob of bytes identified by a tag, the format reserves tags 32768 and above
+/// for Truevision so applications should use tags below that.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeveloperArea {
+    fields: BTreeMap<u16, Vec<u8>>,
+}
+
+impl DeveloperArea {
+    /// Creates a developer area with no fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the data of the field with `tag`
+    ///
+    /// ## returns
+    ///
+    /// - the previous data of the field if it was already set
+    pub fn insert<D>(&mut self, tag: u16, data: D) -> Option<Vec<u8>>
+    where
+        D: Into<Vec<u8>>,
+    {
+        self.fields.insert(tag, data.into())
+    }
+
+    /// The data of the field with `tag`
+    pub fn get(&self, tag: u16) -> Option<&[u8]> {
+        self.fields.get(&tag).map(Vec::as_slice)
+    }
+
+    /// The data of the field with `tag` as text, None if it isn't set or isn't valid UTF-8
+    pub fn get_str(&self, tag: u16) -> Option<&str> {
+        self.get(tag)
+            .and_then(|data| std::str::from_utf8(data).ok())
+    }
+
+    /// Removes the field with `tag`, returning its data if it was set
+    pub fn remove(&mut self, tag: u16) -> Option<Vec<u8>> {
+        self.fields.remove(&tag)
+    }
+
+    /// The tags and data of every field, in order of tag
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &[u8])> {
+        self.fields
+            .iter()
+            .map(|(&tag, data)| (tag, data.as_slice()))
+    }
+
+    /// The number of fields
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Is true if there are no fields
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Reads the directory at `offset` from `start` and every field it lists, an offset of 0
+    /// means there is no developer area
+    ///
+    /// Fields can point at the same bytes, so together they can't be larger than the file or
+    /// `limits`. The reader is left in an unspecified position
+    pub(crate) fn from_seekable_reader<R>(
+        mut reader: R,
+        start: u64,
+        offset: u32,
+        limits: &DecodeLimits,
+    ) -> Result<Self, TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        let mut area = Self::new();
+        if offset == 0 {
+            return Ok(area);
+        }
+
+        let file_size = reader.seek(SeekFrom::End(0))?.saturating_sub(start);
+        let mut buf = [0u8; 2];
+        reader.seek(SeekFrom::Start(start + offset as u64))?;
+        reader.read_exact(&mut buf)?;
+        let count = u16::from_le_bytes(buf);
+
+        let mut directory = vec![0u8; count as usize * 10];
+        reader.read_exact(&mut directory)?;
+
+        let mut total = 0u64;
+        for entry in directory.chunks_exact(10) {
+            let tag = u16::from_le_bytes([entry[0], entry[1]]);
+            let offset = u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]]);
+            let size = u32::from_le_bytes([entry[6], entry[7], entry[8], entry[9]]);
+
+            total += size as u64;
+            if total > file_size {
+                return Err(TgaError::BadExtensionArea(
+                    "developer fields are larger than the file",
+                ));
+            }
+            limits.check_trailer(total)?;
+
+            let mut data = Vec::new();
+            reader.seek(SeekFrom::Start(start + offset as u64))?;
+            (&mut reader).take(size as u64).read_to_end(&mut data)?;
+            if data.len() != size as usize {
+                return Err(TgaError::Truncated);
+            }
+            area.fields.insert(tag, data);
+        }
+
+        Ok(area)
+    }
+
+    /// Writes every field followed by the directory, `position` is the offset of the first byte
+    /// written
+    ///
+    /// ## returns
+    ///
+    /// - the offset of the directory
+    pub(crate) fn write<W>(&self, mut output: W, mut position: u32) -> Result<u32, TgaError>
+    where
+        W: io::Write,
+    {
+        let too_large = || TgaError::InvalidInput("developer area is too large");
+
+        let count = u16::try_from(self.fields.len()).map_err(|_| too_large())?;
+        let mut directory = Vec::with_capacity(2 + self.fields.len() * 10);
+        directory.extend_from_slice(&count.to_le_bytes());
+
+        for (&tag, data) in &self.fields {
+            let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+            output.write_all(data)?;
+
+            directory.extend_from_slice(&tag.to_le_bytes());
+            directory.extend_from_slice(&position.to_le_bytes());
+            directory.extend_from_slice(&size.to_le_bytes());
+            position = position.checked_add(size).ok_or_else(too_large)?;
+        }
+
+        output.write_all(&directory)?;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{Color, DecodeLimits, Image, ReadOptions, TgaError};
+
+    #[test]
+    fn write_read_developer_area() {
+        let mut image = Image::new(4, 2, 3);
+        image.set(2, 1, Color::rgb(1, 2, 3));
+        let area = image.developer_area_mut();
+        area.insert(1, "perspective 45 0.1 100");
+        area.insert(2, "flat");
+        assert_eq!(area.insert(2, "gouraud"), Some(b"flat".to_vec()));
+        area.insert(3, vec![0xff, 0, 0x80]);
+
+        let path = std::env::temp_dir().join("tga_image_write_read_developer_area.tga");
+        image.write_tga_file(&path, None).unwrap();
+        let read = Image::read_tga_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.developer_area(), image.developer_area());
+        assert_eq!(read.developer_area().get_str(2), Some("gouraud"));
+        assert_eq!(read.developer_area().get_str(3), None);
+        assert_eq!(read.developer_area().len(), 3);
+    }
+
+    #[test]
+    fn read_shared_fields() {
+        // 1x1 grey scale, then 50 bytes of field data
+        let mut file = vec![0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 8, 0, 128];
+        let data_offset = file.len() as u32;
+        file.extend_from_slice(&[7; 50]);
+
+        // every field in the directory points at the same data
+        let with_fields = |count: u16| {
+            let mut file = file.clone();
+            let developer_offset = file.len() as u32;
+            file.extend_from_slice(&count.to_le_bytes());
+            for tag in 0..count {
+                file.extend_from_slice(&tag.to_le_bytes());
+                file.extend_from_slice(&data_offset.to_le_bytes());
+                file.extend_from_slice(&50u32.to_le_bytes());
+            }
+            file.extend_from_slice(&[0; 4]);
+            file.extend_from_slice(&developer_offset.to_le_bytes());
+            file.extend_from_slice(b"TRUEVISION-XFILE.\0");
+            file
+        };
+        let read = |file: &[u8], limits| {
+            let options = ReadOptions::new().limits(limits);
+            Image::from_seekable_reader_lenient(Cursor::new(file), &options).unwrap()
+        };
+
+        let (image, warnings) = read(&with_fields(2), DecodeLimits::new());
+        assert!(warnings.is_empty());
+        assert_eq!(image.developer_area().get(1), Some(&[7; 50][..]));
+
+        let (image, warnings) = read(&with_fields(1000), DecodeLimits::new());
+        assert!(image.developer_area().is_empty());
+        assert!(matches!(warnings[..], [TgaError::BadExtensionArea(_)]));
+
+        let (image, warnings) = read(&with_fields(2), DecodeLimits::new().max_bytes(80));
+        assert!(image.developer_area().is_empty());
+        assert!(matches!(warnings[..], [TgaError::LimitExceeded(_)]));
+        assert!(Image::from_bytes(&with_fields(1000)).is_ok());
+    }
+}
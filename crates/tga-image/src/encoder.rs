@@ -0,0 +1,293 @@
+//! Writing an image one row at a time
+
+use std::io::{self, Write};
+
+use crate::{
+    AlphaMode, CountingWriter, DeveloperArea, Encoding, Image, Metadata, TgaError, TgaFile,
+    TgaHeader, WriteOptions,
+};
+
+/// # Writes an image one row at a time
+///
+/// The size and format of the image are given up front, then each row is written with
+/// [`TgaEncoder::write_row`] and [`TgaEncoder::finish`] writes the metadata, developer area and
+/// footer, so only one row of the image needs to be held in memory.
+///
+/// Rows are written in the orientation of the options, by default
+/// [`Orientation::BottomLeft`](crate::Orientation::BottomLeft) so the first row is the bottom of
+/// the image. Run length packets don't continue from one row to the next, with
+/// [`Encoding::RleScanline`] a table of where each row starts is also written. Writing is done
+/// in small pieces so the output should be buffered.
+#[derive(Debug)]
+pub struct TgaEncoder<W: io::Write> {
+    output: CountingWriter<W>,
+    header: TgaHeader,
+    bytes_pp: usize,
+    encoding: Option<Encoding>,
+    id: Vec<u8>,
+    metadata: Option<Metadata>,
+    developer_area: DeveloperArea,
+    scan_lines: Vec<u32>,
+    rows_written: usize,
+}
+
+impl<W: io::Write> TgaEncoder<W> {
+    /// Starts writing an image of `bytes_pp` bytes per pixel, nothing is written until the first
+    /// row
+    ///
+    /// ## Errors
+    ///
+    /// - if the format isn't supported, the width or height is over 32767, or the options ask
+    ///   for a thumbnail or [`Encoding::Auto`] which need the whole image,
+    ///   [`TgaError::UnsupportedDepth`] or [`TgaError::InvalidInput`] is returned
+    pub fn new<O>(
+        output: W,
+        width: usize,
+        height: usize,
+        bytes_pp: usize,
+        options: O,
+    ) -> Result<Self, TgaError>
+    where
+        O: Into<WriteOptions>,
+    {
+        let options = options.into();
+
+        if !matches!(bytes_pp, 1 | 3 | 4) {
+            return Err(TgaError::UnsupportedDepth(bytes_pp as u8 * 8));
+        }
+        if options.thumbnail {
+            return Err(TgaError::InvalidInput("a thumbnail needs the whole image"));
+        }
+        if let Some(Encoding::Auto) = options.encoding {
+            return Err(TgaError::InvalidInput(
+                "choosing the encoding needs the whole image",
+            ));
+        }
+
+        let orientation = options.orientation.unwrap_or_default();
+        let header = options.header(width, height, bytes_pp, orientation, (0, 0))?;
+
+        Ok(Self {
+            output: CountingWriter::new(output),
+            header,
+            bytes_pp,
+            encoding: options.encoding,
+            id: Vec::new(),
+            metadata: None,
+            developer_area: DeveloperArea::new(),
+            scan_lines: Vec::new(),
+            rows_written: 0,
+        })
+    }
+
+    /// Sets the image ID field, see [`Image::set_id`]
+    ///
+    /// ## returns
+    ///
+    /// - false if the ID is too long or a row has already been written, the ID is left unchanged
+    pub fn set_id<I>(&mut self, id: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let id = id.as_ref();
+        if id.len() > u8::MAX as usize || self.rows_written > 0 {
+            return false;
+        }
+        self.id = id.to_vec();
+        true
+    }
+
+    /// Sets the position on screen of the image's lower left corner, see [`Image::set_origin`]
+    ///
+    /// ## returns
+    ///
+    /// - false if a row has already been written, the origin is left unchanged
+    pub fn set_origin(&mut self, x: u16, y: u16) -> bool {
+        if self.rows_written > 0 {
+            return false;
+        }
+        self.header.x_origin = x as i16;
+        self.header.y_origin = y as i16;
+        true
+    }
+
+    /// Sets the metadata to write in the extension area, `None` doesn't write one
+    pub fn set_metadata<M>(&mut self, metadata: M)
+    where
+        M: Into<Option<Metadata>>,
+    {
+        self.metadata = metadata.into();
+    }
+
+    /// The developer area to write, see [`Image::developer_area_mut`]
+    pub fn developer_area_mut(&mut self) -> &mut DeveloperArea {
+        &mut self.developer_area
+    }
+
+    /// The number of rows left to write
+    pub fn rows_remaining(&self) -> usize {
+        self.header.height as usize - self.rows_written
+    }
+
+    /// Writes the next row, with `bytes_pp` bytes for each pixel
+    ///
+    /// ## Errors
+    ///
+    /// - any error from writing
+    /// - if the row is the wrong length or every row has been written [`TgaError::InvalidInput`]
+    ///   is returned
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), TgaError> {
+        if row.len() != self.header.width as usize * self.bytes_pp {
+            return Err(TgaError::InvalidInput("row is the wrong length"));
+        }
+        if self.rows_remaining() == 0 {
+            return Err(TgaError::InvalidInput("every row has already been written"));
+        }
+
+        if self.rows_written == 0 {
+            self.write_start()?;
+        }
+
+        let packed;
+        let row = if self.header.bytes_pp() == 2 {
+            packed = Image::to_a1r5g5b5_data(row, self.bytes_pp);
+            &packed[..]
+        } else {
+            row
+        };
+
+        match self.encoding {
+            None => self.output.write_all(row)?,
+            Some(Encoding::Rle) => {
+                Image::save_rle_data(row, self.header.bytes_pp(), &mut self.output)?
+            }
+            Some(Encoding::RleScanline) => {
+                self.scan_lines.push(self.output.position()?);
+                Image::save_rle_data(row, self.header.bytes_pp(), &mut self.output)?
+            }
+            Some(Encoding::Auto) => unreachable!("automatic encoding is rejected when created"),
+        }
+
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    /// Writes the metadata, developer area and footer after the last row
+    ///
+    /// ## returns
+    ///
+    /// - the output once everything has been flushed
+    ///
+    /// ## Errors
+    ///
+    /// - any error from writing
+    /// - if not every row has been written [`TgaError::InvalidInput`] is returned
+    pub fn finish(mut self) -> Result<W, TgaError> {
+        if self.rows_remaining() != 0 {
+            return Err(TgaError::InvalidInput("not every row has been written"));
+        }
+        // an image with no rows still has a header
+        if self.rows_written == 0 {
+            self.write_start()?;
+        }
+
+        // the rows are written as they are given, so any alpha is straight
+        let current = self.metadata.as_ref().map(Metadata::attributes_type);
+        let attributes_type = AlphaMode::Straight
+            .attributes_type(self.header.alpha_bits() != 0, current.unwrap_or_default());
+
+        let file = TgaFile {
+            header: self.header,
+            id: &self.id,
+            color_map: &[],
+            data: &[],
+            encoding: &self.encoding,
+            metadata: self.metadata.as_ref(),
+            attributes_type: Some(attributes_type),
+            developer_area: Some(&self.developer_area),
+            postage_stamp: None,
+        };
+        file.write_end(&mut self.output, &self.scan_lines)?;
+
+        self.output.flush()?;
+        Ok(self.output.into_inner())
+    }
+
+    /// Writes the header and ID before the first row
+    fn write_start(&mut self) -> io::Result<()> {
+        let file = TgaFile {
+            header: self.header,
+            id: &self.id,
+            color_map: &[],
+            data: &[],
+            encoding: &self.encoding,
+            metadata: None,
+            attributes_type: None,
+            developer_area: None,
+            postage_stamp: None,
+        };
+        file.write_start(&mut self.output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Color, Orientation};
+
+    fn rows(image: &Image) -> impl DoubleEndedIterator<Item = &[u8]> {
+        image
+            .as_slice()
+            .chunks_exact(image.width() * image.format())
+    }
+
+    #[test]
+    fn encode_rows() {
+        let mut image = Image::new(7, 5, 4);
+        for y in 0..5 {
+            image.set(y, y, Color::rgba(255, 128, 0, 255));
+        }
+        image.set_id("tile");
+        image.set_metadata(Metadata::builder().job_name("tiles").build());
+        image.developer_area_mut().insert(7, "camera");
+
+        let mut encoder = TgaEncoder::new(Vec::new(), 7, 5, 4, None).unwrap();
+        assert!(encoder.set_id("tile"));
+        encoder.set_metadata(image.metadata().cloned());
+        encoder.developer_area_mut().insert(7, "camera");
+        for row in rows(&image) {
+            encoder.write_row(row).unwrap();
+        }
+        assert!(!encoder.set_id("late"));
+        assert!(encoder.write_row(&[0; 28]).is_err());
+        assert_eq!(encoder.finish().unwrap(), image.to_bytes(None).unwrap());
+
+        let options = WriteOptions::new()
+            .encoding(Encoding::Rle)
+            .bits_per_pixel(16)
+            .orientation(Orientation::TopLeft);
+        let mut encoder = TgaEncoder::new(Vec::new(), 7, 5, 4, options).unwrap();
+        for row in rows(&image).rev() {
+            encoder.write_row(row).unwrap();
+        }
+        let read = Image::from_bytes(&encoder.finish().unwrap()).unwrap();
+        assert_eq!(read.get(4, 0), Some(Color::rgba(255, 132, 0, 255)));
+        assert_eq!(read.get(3, 0), Some(Color::rgba(0, 0, 0, 0)));
+
+        let encoder = TgaEncoder::new(Vec::new(), 7, 5, 4, None).unwrap();
+        assert!(encoder.finish().is_err());
+
+        let encoder = TgaEncoder::new(Vec::new(), 7, 0, 3, None).unwrap();
+        let empty = Image::new(7, 0, 3).to_bytes(None).unwrap();
+        assert_eq!(encoder.finish().unwrap(), empty);
+        assert!(matches!(
+            TgaEncoder::new(Vec::new(), 40000, 1, 1, None),
+            Err(TgaError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Image::new(40000, 1, 1).to_bytes(None),
+            Err(TgaError::InvalidInput(_))
+        ));
+        assert!(Image::new(1, 32767, 1).to_bytes(None).is_ok());
+    }
+}
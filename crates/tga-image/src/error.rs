@@ -0,0 +1,135 @@
+//! The errors from reading and writing images
+
+use std::{error::Error, fmt, io};
+
+/// # The reasons reading or writing an image can fail
+///
+/// This converts into an `io::Error` so it can be used with `?` in functions returning
+/// `io::Result`, the `TgaError` is kept as the inner error.
+#[derive(Debug)]
+pub enum TgaError {
+    /// - Io: reading or writing failed
+    Io(io::Error),
+    /// - BadDimensions: the width or height in the header is zero or negative
+    BadDimensions {
+        /// The width in the header
+        width: i16,
+        /// The height in the header
+        height: i16,
+    },
+    /// - UnsupportedDepth: the number of bits per pixel can't be used with the type of image
+    UnsupportedDepth(u8),
+    /// - UnsupportedDataType: the type of image in the header isn't one that can be read
+    UnsupportedDataType(u8),
+    /// - Truncated: the data ended before the image did
+    Truncated,
+    /// - RleOverrun: a run length packet continues past the end of the image
+    RleOverrun,
+    /// - BadColorMap: the color map is missing or can't be read, or a pixel isn't in it
+    BadColorMap(&'static str),
+    /// - BadExtensionArea: the extension area or developer area can't be read
+    BadExtensionArea(&'static str),
+    /// - LimitExceeded: the image is larger than the [`DecodeLimits`](crate::DecodeLimits) allow
+    LimitExceeded(&'static str),
+    /// - InvalidInput: the image or options can't be written, or an encoder was misused
+    InvalidInput(&'static str),
+}
+
+impl fmt::Display for TgaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => error.fmt(f),
+            Self::BadDimensions { width, height } => {
+                write!(f, "invalid dimensions {}x{}", width, height)
+            }
+            Self::UnsupportedDepth(bits) => write!(f, "unsupported bits per pixel {}", bits),
+            Self::UnsupportedDataType(code) => write!(f, "unknown file format {:x}", code),
+            Self::Truncated => f.write_str("image data is truncated"),
+            Self::RleOverrun => f.write_str("run length packet overruns the image"),
+            Self::BadColorMap(reason)
+            | Self::BadExtensionArea(reason)
+            | Self::LimitExceeded(reason)
+            | Self::InvalidInput(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl Error for TgaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TgaError {
+    fn from(error: io::Error) -> Self {
+        if error.get_ref().is_some_and(|inner| inner.is::<TgaError>()) {
+            let inner = error.into_inner().expect("the inner error was checked");
+            return *inner.downcast().expect("the inner error was checked");
+        }
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(error),
+        }
+    }
+}
+
+impl From<TgaError> for io::Error {
+    fn from(error: TgaError) -> Self {
+        let kind = match error {
+            TgaError::Io(error) => return error,
+            TgaError::Truncated => io::ErrorKind::UnexpectedEof,
+            TgaError::InvalidInput(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Encoding, Image};
+
+    #[test]
+    fn error_causes() {
+        let mut file = Image::new(4, 2, 3).to_bytes(Encoding::Rle).unwrap();
+
+        assert!(matches!(
+            Image::from_bytes(&file[..20]),
+            Err(TgaError::Truncated)
+        ));
+
+        // a run of 9 pixels where 8 fit
+        file[18] = 0x88;
+        assert!(matches!(
+            Image::from_reader(&file[..]),
+            Err(TgaError::RleOverrun)
+        ));
+
+        file[2] = 4;
+        assert!(matches!(
+            Image::from_reader(&file[..]),
+            Err(TgaError::UnsupportedDataType(4))
+        ));
+
+        file[12] = 0;
+        let error = Image::from_reader(&file[..]).unwrap_err();
+        assert!(matches!(
+            error,
+            TgaError::BadDimensions {
+                width: 0,
+                height: 2
+            }
+        ));
+
+        let error = io::Error::from(error);
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            TgaError::from(error),
+            TgaError::BadDimensions { .. }
+        ));
+    }
+}
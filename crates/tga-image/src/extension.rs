@@ -0,0 +1,656 @@
+//! The TGA 2.0 footer and extension area
+
+use std::{
+    io::{self, SeekFrom},
+    time::{Duration, SystemTime},
+};
+
+use crate::{Color, DecodeLimits, TgaError};
+
+const FOOTER_SIZE: usize = 26;
+const SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+pub(crate) const EXTENSION_SIZE: usize = 495;
+
+/// The offsets stored in the footer of a TGA 2.0 file, relative to the start of the file
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Footer {
+    pub(crate) extension_offset: u32,
+    pub(crate) developer_offset: u32,
+}
+
+impl Footer {
+    /// Reads the footer at the end of a file, the offsets are relative to `start`
+    ///
+    /// ## returns
+    ///
+    /// - None if the file doesn't end with a TGA 2.0 footer
+    pub(crate) fn from_reader<R>(mut reader: R, start: u64) -> Result<Option<Self>, TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < start + FOOTER_SIZE as u64 {
+            return Ok(None);
+        }
+
+        let mut buf = [0u8; FOOTER_SIZE];
+        reader.seek(SeekFrom::Start(end - FOOTER_SIZE as u64))?;
+        reader.read_exact(&mut buf)?;
+
+        if &buf[8..] != SIGNATURE {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            extension_offset: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            developer_offset: u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+        }))
+    }
+
+    pub(crate) fn write<W>(&self, mut output: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        output.write_all(&self.extension_offset.to_le_bytes())?;
+        output.write_all(&self.developer_offset.to_le_bytes())?;
+        output.write_all(SIGNATURE)
+    }
+}
+
+/// The date and time an image was saved
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    /// The full year, such as 2021
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    /// 0 to 23
+    pub hour: u8,
+    /// 0 to 59
+    pub minute: u8,
+    /// 0 to 59
+    pub second: u8,
+}
+
+impl Timestamp {
+    /// The current date and time in UTC
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self::from_unix_time(seconds)
+    }
+
+    fn from_unix_time(seconds: u64) -> Self {
+        let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+        // converts days since 1970-01-01 into a civil date, the years start in March so the
+        // leap day is at the end
+        let days = days as i64 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+}
+
+/// The version of the software that created an image, such as 4.17b
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SoftwareVersion {
+    /// The version multiplied by 100, so 4.17 is 417
+    pub number: u16,
+    /// An optional letter following the number
+    pub letter: Option<char>,
+}
+
+/// # What the alpha channel of an image represents
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AttributesType {
+    /// - NoAlpha: there is no alpha data
+    #[default]
+    NoAlpha,
+    /// - UndefinedIgnore: the alpha data can be ignored
+    UndefinedIgnore,
+    /// - UndefinedRetain: the alpha data should be kept but isn't meaningful
+    UndefinedRetain,
+    /// - Alpha: the color channels are not multiplied by alpha
+    Alpha,
+    /// - PremultipliedAlpha: the color channels are multiplied by alpha
+    PremultipliedAlpha,
+    /// - Other: a reserved or unassigned value
+    Other(u8),
+}
+
+impl From<AttributesType> for u8 {
+    fn from(value: AttributesType) -> Self {
+        match value {
+            AttributesType::NoAlpha => 0,
+            AttributesType::UndefinedIgnore => 1,
+            AttributesType::UndefinedRetain => 2,
+            AttributesType::Alpha => 3,
+            AttributesType::PremultipliedAlpha => 4,
+            AttributesType::Other(other) => other,
+        }
+    }
+}
+
+impl From<u8> for AttributesType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::NoAlpha,
+            1 => Self::UndefinedIgnore,
+            2 => Self::UndefinedRetain,
+            3 => Self::Alpha,
+            4 => Self::PremultipliedAlpha,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// # The metadata of a TGA 2.0 extension area
+///
+/// Text fields are limited in length by the format and are empty if not set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    author_name: String,
+    author_comments: Vec<String>,
+    timestamp: Option<Timestamp>,
+    job_name: String,
+    job_time: Duration,
+    software_id: String,
+    software_version: Option<SoftwareVersion>,
+    key_color: Color,
+    pixel_aspect_ratio: Option<(u16, u16)>,
+    gamma: Option<(u16, u16)>,
+    pub(crate) attributes_type: AttributesType,
+    pub(crate) color_correction_offset: u32,
+    pub(crate) postage_stamp_offset: u32,
+    pub(crate) scan_line_offset: u32,
+    pub(crate) scan_line_table: Vec<u32>,
+}
+
+impl Metadata {
+    /// Starts building metadata with every field unset
+    pub fn builder() -> MetadataBuilder {
+        MetadataBuilder::new()
+    }
+
+    /// Reads the extension area of an image at `offset` from `start`, an offset of 0 means there
+    /// is no extension area, along with the scan line table of an image with `height` rows if it
+    /// fits in `limits`
+    ///
+    /// The reader is left in an unspecified position
+    pub(crate) fn from_seekable_reader<R>(
+        mut reader: R,
+        start: u64,
+        offset: u32,
+        height: usize,
+        limits: &DecodeLimits,
+    ) -> Result<Option<Self>, TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let mut buf = [0u8; EXTENSION_SIZE];
+        reader.seek(SeekFrom::Start(start + offset as u64))?;
+        reader.read_exact(&mut buf)?;
+        let mut metadata = Self::from_buffer(&buf)?;
+
+        if metadata.scan_line_offset != 0 {
+            limits.check_trailer(height as u64 * 4)?;
+            let mut table = vec![0u8; height * 4];
+            reader.seek(SeekFrom::Start(start + metadata.scan_line_offset as u64))?;
+            reader.read_exact(&mut table)?;
+            metadata.scan_line_table = table
+                .chunks_exact(4)
+                .map(|offset| u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]))
+                .collect();
+        }
+
+        Ok(Some(metadata))
+    }
+
+    fn from_buffer(buf: &[u8; EXTENSION_SIZE]) -> Result<Self, TgaError> {
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+
+        if (u16_at(0) as usize) < EXTENSION_SIZE {
+            return Err(TgaError::BadExtensionArea("extension area is too small"));
+        }
+
+        let timestamp = Timestamp {
+            month: u16_at(367) as u8,
+            day: u16_at(369) as u8,
+            year: u16_at(371),
+            hour: u16_at(373) as u8,
+            minute: u16_at(375) as u8,
+            second: u16_at(377) as u8,
+        };
+
+        let job_time = u16_at(420) as u64 * 3600 + u16_at(422) as u64 * 60 + u16_at(424) as u64;
+
+        let software_version = match (u16_at(467), buf[469]) {
+            (0, b' ') | (0, 0) => None,
+            (number, b' ') | (number, 0) => Some(SoftwareVersion {
+                number,
+                letter: None,
+            }),
+            (number, letter) => Some(SoftwareVersion {
+                number,
+                letter: Some(letter as char),
+            }),
+        };
+
+        let ratio = |i: usize| match (u16_at(i), u16_at(i + 2)) {
+            (_, 0) => None,
+            ratio => Some(ratio),
+        };
+
+        let mut author_comments = buf[43..367].chunks_exact(81).map(text).collect::<Vec<_>>();
+        while author_comments.last().is_some_and(String::is_empty) {
+            author_comments.pop();
+        }
+
+        Ok(Self {
+            author_name: text(&buf[2..43]),
+            author_comments,
+            timestamp: Some(timestamp).filter(|t| *t != Timestamp::default()),
+            job_name: text(&buf[379..420]),
+            job_time: Duration::from_secs(job_time),
+            software_id: text(&buf[426..467]),
+            software_version,
+            key_color: Color::rgba(buf[472], buf[471], buf[470], buf[473]),
+            pixel_aspect_ratio: ratio(474),
+            gamma: ratio(478),
+            color_correction_offset: u32_at(482),
+            postage_stamp_offset: u32_at(486),
+            scan_line_offset: u32_at(490),
+            scan_line_table: Vec::new(),
+            attributes_type: buf[494].into(),
+        })
+    }
+
+    pub(crate) fn to_buffer(&self) -> [u8; EXTENSION_SIZE] {
+        let mut buf = [0u8; EXTENSION_SIZE];
+        let mut put = |i: usize, bytes: &[u8]| buf[i..][..bytes.len()].copy_from_slice(bytes);
+
+        put(0, &(EXTENSION_SIZE as u16).to_le_bytes());
+        put(2, self.author_name.as_bytes());
+        for (i, line) in self.author_comments.iter().enumerate() {
+            put(43 + i * 81, line.as_bytes());
+        }
+
+        if let Some(timestamp) = self.timestamp {
+            let fields = [
+                timestamp.month as u16,
+                timestamp.day as u16,
+                timestamp.year,
+                timestamp.hour as u16,
+                timestamp.minute as u16,
+                timestamp.second as u16,
+            ];
+            for (i, field) in fields.iter().enumerate() {
+                put(367 + i * 2, &field.to_le_bytes());
+            }
+        }
+
+        put(379, self.job_name.as_bytes());
+        let seconds = self.job_time.as_secs();
+        let hours = (seconds / 3600).min(u16::MAX as u64) as u16;
+        put(420, &hours.to_le_bytes());
+        put(422, &((seconds / 60 % 60) as u16).to_le_bytes());
+        put(424, &((seconds % 60) as u16).to_le_bytes());
+
+        put(426, self.software_id.as_bytes());
+        let (number, letter) = self.software_version.map_or((0, ' '), |version| {
+            (version.number, version.letter.unwrap_or(' '))
+        });
+        put(467, &number.to_le_bytes());
+        put(469, &[letter as u8]);
+
+        let [b, g, r, a] = self.key_color.bgra;
+        put(470, &[b, g, r, a]);
+        let (numerator, denominator) = self.pixel_aspect_ratio.unwrap_or((0, 0));
+        put(474, &numerator.to_le_bytes());
+        put(476, &denominator.to_le_bytes());
+        let (numerator, denominator) = self.gamma.unwrap_or((0, 0));
+        put(478, &numerator.to_le_bytes());
+        put(480, &denominator.to_le_bytes());
+
+        put(482, &self.color_correction_offset.to_le_bytes());
+        put(486, &self.postage_stamp_offset.to_le_bytes());
+        put(490, &self.scan_line_offset.to_le_bytes());
+        put(494, &[self.attributes_type.into()]);
+
+        buf
+    }
+
+    /// The name of the author
+    pub fn author_name(&self) -> &str {
+        &self.author_name
+    }
+
+    /// Up to 4 lines of comments from the author
+    pub fn author_comments(&self) -> &[String] {
+        &self.author_comments
+    }
+
+    /// When the image was saved
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// The name or ID of the job the image belongs to
+    pub fn job_name(&self) -> &str {
+        &self.job_name
+    }
+
+    /// The time spent on the job, to the second
+    pub fn job_time(&self) -> Duration {
+        self.job_time
+    }
+
+    /// The name of the software that created the image
+    pub fn software_id(&self) -> &str {
+        &self.software_id
+    }
+
+    /// The version of the software that created the image
+    pub fn software_version(&self) -> Option<SoftwareVersion> {
+        self.software_version
+    }
+
+    /// The background or transparent color, as RGBA
+    pub fn key_color(&self) -> Color {
+        self.key_color
+    }
+
+    /// The width of a pixel relative to its height, as a numerator and denominator
+    pub fn pixel_aspect_ratio(&self) -> Option<(u16, u16)> {
+        self.pixel_aspect_ratio
+    }
+
+    /// The gamma the image was saved with, as a numerator and denominator
+    pub fn gamma(&self) -> Option<(u16, u16)> {
+        self.gamma
+    }
+
+    /// What the alpha channel represents
+    pub fn attributes_type(&self) -> AttributesType {
+        self.attributes_type
+    }
+
+    /// The offset from the start of the file to each row, in the order the rows are stored, so
+    /// a row can be read without decoding the rows before it
+    ///
+    /// This is empty unless the file has a scan line table, which is written with
+    /// [`Encoding::RleScanline`](crate::Encoding::RleScanline)
+    pub fn scan_line_table(&self) -> &[u32] {
+        &self.scan_line_table
+    }
+}
+
+/// # Builds the metadata to write to an extension area
+///
+/// Text that is too long for its field is cut short
+#[derive(Debug, Clone)]
+pub struct MetadataBuilder {
+    metadata: Metadata,
+}
+
+impl Default for MetadataBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetadataBuilder {
+    /// Starts with every field unset
+    pub fn new() -> Self {
+        Self {
+            metadata: Metadata {
+                author_name: String::new(),
+                author_comments: Vec::new(),
+                timestamp: None,
+                job_name: String::new(),
+                job_time: Duration::default(),
+                software_id: String::new(),
+                software_version: None,
+                key_color: Color::rgba(0, 0, 0, 0),
+                pixel_aspect_ratio: None,
+                gamma: None,
+                attributes_type: AttributesType::NoAlpha,
+                color_correction_offset: 0,
+                postage_stamp_offset: 0,
+                scan_line_offset: 0,
+                scan_line_table: Vec::new(),
+            },
+        }
+    }
+
+    /// Sets the name of the author, up to 40 bytes
+    pub fn author_name(mut self, name: &str) -> Self {
+        self.metadata.author_name = truncate(name, 40);
+        self
+    }
+
+    /// Sets the comments from the author, up to 4 lines of 80 bytes
+    pub fn author_comments(mut self, comments: &str) -> Self {
+        self.metadata.author_comments = comments
+            .lines()
+            .take(4)
+            .map(|line| truncate(line, 80))
+            .collect();
+        self
+    }
+
+    /// Sets when the image was saved, see [`Timestamp::now`]
+    pub fn timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.metadata.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets the name or ID of the job, up to 40 bytes
+    pub fn job_name(mut self, name: &str) -> Self {
+        self.metadata.job_name = truncate(name, 40);
+        self
+    }
+
+    /// Sets the time spent on the job, this is kept to the second
+    pub fn job_time(mut self, time: Duration) -> Self {
+        self.metadata.job_time = Duration::from_secs(time.as_secs());
+        self
+    }
+
+    /// Sets the name of the software, up to 40 bytes
+    pub fn software_id(mut self, id: &str) -> Self {
+        self.metadata.software_id = truncate(id, 40);
+        self
+    }
+
+    /// Sets the version of the software
+    pub fn software_version(mut self, version: SoftwareVersion) -> Self {
+        self.metadata.software_version = Some(version);
+        self
+    }
+
+    /// Sets the background or transparent color
+    pub fn key_color(mut self, color: Color) -> Self {
+        let [b, g, r, a] = match color.bytes_pp {
+            1 => [color.bgra[0], color.bgra[0], color.bgra[0], 255],
+            3 => [color.bgra[0], color.bgra[1], color.bgra[2], 255],
+            _ => color.bgra,
+        };
+        self.metadata.key_color = Color::rgba(r, g, b, a);
+        self
+    }
+
+    /// Sets the width of a pixel relative to its height
+    pub fn pixel_aspect_ratio(mut self, numerator: u16, denominator: u16) -> Self {
+        self.metadata.pixel_aspect_ratio = Some((numerator, denominator)).filter(|r| r.1 != 0);
+        self
+    }
+
+    /// Sets the gamma, as a numerator and denominator
+    pub fn gamma(mut self, numerator: u16, denominator: u16) -> Self {
+        self.metadata.gamma = Some((numerator, denominator)).filter(|r| r.1 != 0);
+        self
+    }
+
+    /// Sets what the alpha channel represents
+    pub fn attributes_type(mut self, attributes_type: AttributesType) -> Self {
+        self.metadata.attributes_type = attributes_type;
+        self
+    }
+
+    /// Finishes building the metadata
+    pub fn build(self) -> Metadata {
+        self.metadata
+    }
+}
+
+/// Cuts text to at most `len` bytes without splitting a character
+fn truncate(text: &str, len: usize) -> String {
+    let mut end = text.len().min(len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].trim_end().to_owned()
+}
+
+/// Reads a fixed size, null terminated text field
+fn text(field: &[u8]) -> String {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Image;
+
+    #[test]
+    fn read_extension_area() {
+        #[rustfmt::skip]
+        let mut file = vec![
+            // 1x1 grey scale
+            0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 8, 0, 128,
+        ];
+        let extension_offset = file.len() as u32;
+
+        let mut extension = [0u8; EXTENSION_SIZE];
+        extension[..2].copy_from_slice(&(EXTENSION_SIZE as u16).to_le_bytes());
+        extension[2..8].copy_from_slice(b"Kieran");
+        extension[43..48].copy_from_slice(b"first");
+        extension[124..130].copy_from_slice(b"second");
+        extension[367..379].copy_from_slice(&[3, 0, 14, 0, 0xe5, 0x07, 12, 0, 30, 0, 5, 0]);
+        extension[420..426].copy_from_slice(&[1, 0, 2, 0, 3, 0]);
+        extension[426..434].copy_from_slice(b"renderer");
+        extension[467..470].copy_from_slice(&[0xa1, 0x01, b'b']);
+        extension[470..474].copy_from_slice(&[1, 2, 3, 4]);
+        extension[478..482].copy_from_slice(&[22, 0, 10, 0]);
+        extension[494] = 3;
+        file.extend_from_slice(&extension);
+
+        file.extend_from_slice(&extension_offset.to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(SIGNATURE);
+
+        let image = Image::from_seekable_reader(io::Cursor::new(&file)).unwrap();
+        let metadata = image.metadata().unwrap();
+
+        assert_eq!(metadata.author_name(), "Kieran");
+        assert_eq!(metadata.author_comments(), &["first", "second"]);
+        let timestamp = metadata.timestamp().unwrap();
+        assert_eq!(
+            (timestamp.year, timestamp.month, timestamp.day),
+            (2021, 3, 14)
+        );
+        assert_eq!(metadata.job_time(), Duration::from_secs(3723));
+        assert_eq!(metadata.software_id(), "renderer");
+        let version = metadata.software_version().unwrap();
+        assert_eq!((version.number, version.letter), (417, Some('b')));
+        assert_eq!(metadata.key_color(), Color::rgba(3, 2, 1, 4));
+        assert_eq!(metadata.pixel_aspect_ratio(), None);
+        assert_eq!(metadata.gamma(), Some((22, 10)));
+        assert_eq!(metadata.attributes_type(), AttributesType::Alpha);
+
+        // the pixels are still read if what follows them isn't valid
+        let mut bad = file.clone();
+        bad[extension_offset as usize] = 7;
+        let footer = bad.len() - FOOTER_SIZE;
+        bad[footer + 4..footer + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let image = Image::from_bytes(&bad).unwrap();
+        assert!(image.metadata().is_none());
+        assert!(image.developer_area().is_empty());
+        assert_eq!(image.get(0, 0), Some(Color::grey_scale(128)));
+        bad[footer..footer + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Image::from_bytes(&bad).unwrap().metadata().is_none());
+        let (_, warnings) =
+            Image::from_seekable_reader_lenient(io::Cursor::new(&bad), &crate::ReadOptions::new())
+                .unwrap();
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn write_read_metadata() {
+        let metadata = Metadata::builder()
+            .author_name("Kieran")
+            .author_comments("rendered with\nflat shading")
+            .timestamp(Timestamp::now())
+            .job_name("african head")
+            .job_time(Duration::from_millis(90_500))
+            .software_id("renderer")
+            .software_version(SoftwareVersion {
+                number: 1,
+                letter: None,
+            })
+            .key_color(Color::rgb(255, 0, 255))
+            .gamma(22, 10)
+            .build();
+
+        let mut image = Image::new(2, 2, 3);
+        image.set_metadata(metadata.clone());
+
+        let path = std::env::temp_dir().join("tga_image_write_read_metadata.tga");
+        image.write_tga_file(&path, None).unwrap();
+        let read = Image::read_tga_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.metadata(), Some(&metadata));
+        assert_eq!(metadata.job_time(), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn civil_timestamp() {
+        let leap_day = Timestamp {
+            year: 2020,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 59,
+            second: 58,
+        };
+        assert_eq!(Timestamp::from_unix_time(1_583_020_798), leap_day);
+        assert_eq!(Timestamp::from_unix_time(0).year, 1970);
+    }
+}
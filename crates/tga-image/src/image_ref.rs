@@ -0,0 +1,197 @@
+//! Viewing the pixels of an uncompressed image without copying them
+
+use crate::{AlphaMode, Color, DeveloperArea, Image, Orientation, TgaError, TgaHeader};
+
+/// # An uncompressed image borrowed from the bytes of a TGA file
+///
+/// The pixels are used where they are in the buffer, for example a memory mapped file, so
+/// nothing is decoded or copied. This only works for grey scale, RGB and RGBA images that aren't
+/// run length encoded, other images need to be read with [`Image::from_bytes`].
+///
+/// Unlike [`Image`] the pixels are never reoriented, they are in the order they are stored, see
+/// [`ImageRef::orientation`].
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRef<'a> {
+    width: usize,
+    height: usize,
+    bytes_pp: usize,
+    data: &'a [u8],
+    id: &'a [u8],
+    orientation: Orientation,
+    origin: (u16, u16),
+    alpha_mode: AlphaMode,
+}
+
+impl<'a> ImageRef<'a> {
+    /// Views the image at the start of `bytes`
+    ///
+    /// ## Errors
+    ///
+    /// - if the data ends before the image does [`TgaError::Truncated`] is returned
+    /// - if the image is compressed, color mapped or 15 or 16 bits per pixel
+    ///   [`TgaError::UnsupportedDataType`] or [`TgaError::UnsupportedDepth`] is returned
+    /// - if the header isn't valid the matching [`TgaError`] is returned
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, TgaError> {
+        let header = TgaHeader::from_reader(bytes)?;
+
+        if !matches!(header.data_type_code, 2 | 3) {
+            return Err(TgaError::UnsupportedDataType(header.data_type_code));
+        }
+        if !matches!(header.bits_per_pixel, 8 | 24 | 32) {
+            return Err(TgaError::UnsupportedDepth(header.bits_per_pixel));
+        }
+
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let bytes_pp = header.bytes_pp();
+        let color_map_size = if header.color_map_type == 0 {
+            0
+        } else {
+            (header.color_map_depth as usize).div_ceil(8) * header.color_map_length as usize
+        };
+
+        let rest = &bytes[18..];
+        let (id, rest) = split(rest, header.id_len as usize)?;
+        let (_, rest) = split(rest, color_map_size)?;
+        let (data, _) = split(rest, width * height * bytes_pp)?;
+
+        Ok(Self {
+            width,
+            height,
+            bytes_pp,
+            data,
+            id,
+            orientation: Orientation::from_descriptor(header.image_descriptor),
+            origin: (header.x_origin as u16, header.y_origin as u16),
+            alpha_mode: AlphaMode::from_alpha_bits(header.alpha_bits(), bytes_pp),
+        })
+    }
+
+    /// Access a single pixel within the image
+    ///
+    /// ## returns
+    ///
+    /// - Some(Color) if the indicies are inside the image area
+    /// - None otherwise
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Color::try_from_slice(&self.data[((x + y * self.width) * self.bytes_pp)..][..self.bytes_pp])
+    }
+
+    /// The bytes of a single row
+    ///
+    /// ## returns
+    ///
+    /// - Some(row) if the row is inside the image area
+    /// - None otherwise
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let row_size = self.width * self.bytes_pp;
+        Some(&self.data[y * row_size..][..row_size])
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of bytes per pixel
+    pub fn format(&self) -> usize {
+        self.bytes_pp
+    }
+
+    /// The pixels as they are stored in the file
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The image ID field, see [`Image::id`]
+    pub fn id(&self) -> &'a [u8] {
+        self.id
+    }
+
+    /// The order the pixels are stored in, see [`Image::orientation`]
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// The position on screen of the image's lower left corner, see [`Image::origin`]
+    pub fn origin(&self) -> (u16, u16) {
+        self.origin
+    }
+
+    /// How the color channels relate to the alpha, only the alpha bits of the header are used
+    /// as the metadata isn't read, see [`Image::alpha_mode`]
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Copies the pixels into an owned image, in the same orientation
+    pub fn to_image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            bytes_pp: self.bytes_pp,
+            data: self.data.to_vec(),
+            id: self.id.to_vec(),
+            metadata: None,
+            developer_area: DeveloperArea::new(),
+            orientation: self.orientation,
+            origin: self.origin,
+            alpha_mode: self.alpha_mode,
+        }
+    }
+}
+
+/// Splits `n` bytes off the front of `bytes`
+fn split(bytes: &[u8], n: usize) -> Result<(&[u8], &[u8]), TgaError> {
+    if bytes.len() < n {
+        return Err(TgaError::Truncated);
+    }
+    Ok(bytes.split_at(n))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Encoding, WriteOptions};
+
+    #[test]
+    fn borrow_image() {
+        let mut image = Image::new(3, 2, 4);
+        image.set(2, 0, Color::rgba(10, 20, 30, 40));
+        image.set_id("borrowed");
+
+        let options = WriteOptions::new().orientation(Orientation::TopLeft);
+        let bytes = image.to_bytes(options).unwrap();
+        let borrowed = ImageRef::from_bytes(&bytes).unwrap();
+        let read = Image::from_bytes(&bytes).unwrap();
+
+        assert_eq!(borrowed.orientation(), Orientation::TopLeft);
+        assert_eq!(borrowed.id(), b"borrowed");
+        assert_eq!(borrowed.as_slice(), read.as_slice());
+        assert_eq!(borrowed.get(2, 1), Some(Color::rgba(10, 20, 30, 40)));
+        assert_eq!(borrowed.row(1), Some(&read.as_slice()[12..]));
+        assert_eq!(borrowed.row(2), None);
+        assert_eq!(borrowed.to_image().as_slice(), read.as_slice());
+
+        assert!(matches!(
+            ImageRef::from_bytes(&bytes[..40]),
+            Err(TgaError::Truncated)
+        ));
+        let bytes = image.to_bytes(Encoding::Rle).unwrap();
+        assert!(matches!(
+            ImageRef::from_bytes(&bytes),
+            Err(TgaError::UnsupportedDataType(10))
+        ));
+    }
+}
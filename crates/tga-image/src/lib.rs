@@ -4,14 +4,51 @@
 //!
 //! utility for loading [Truevision TGA](https://en.wikipedia.org/wiki/Truevision_TGA) images.
 //! Provide reading and writing of the format via `std::io::Read` and `std::io::Write` and
-//! returns the relevant `io::Result`
+//! returns a [`TgaError`] describing what went wrong, which converts into an `io::Error`
 
 use std::{
+    borrow::Cow,
+    collections::VecDeque,
     fs,
-    io::{self, Write},
+    io::{self, Read, Write},
     path::Path,
 };
 
+mod blend;
+mod color;
+mod color_map;
+mod convert;
+mod decoder;
+mod developer;
+mod encoder;
+mod error;
+mod extension;
+mod image_ref;
+mod limits;
+mod quantize;
+pub mod typed;
+mod view;
+
+pub use blend::{AlphaMode, BlendMode};
+pub use color_map::{ColorMap, IndexedImage};
+pub use convert::{ConvertOptions, LumaWeights, PixelFormat};
+pub use decoder::{Rows, TgaDecoder};
+pub use developer::DeveloperArea;
+pub use encoder::TgaEncoder;
+pub use error::TgaError;
+pub use extension::{AttributesType, Metadata, MetadataBuilder, SoftwareVersion, Timestamp};
+pub use image_ref::ImageRef;
+pub use limits::DecodeLimits;
+pub use view::{ImageView, ImageViewMut};
+
+use decoder::RlePacket;
+use extension::Footer;
+pub use quantize::{Dither, Quantizer};
+
+/// The most pixels the cheapest run length packets are found for at once, packets don't cross
+/// from one chunk to the next so the memory needed doesn't grow with the image
+const RLE_CHUNK: usize = 4096;
+
 #[derive(Debug, Clone, Copy, Default)]
 #[repr(C, packed)]
 struct TgaHeader {
@@ -47,7 +84,7 @@ struct TgaHeader {
 }
 
 impl TgaHeader {
-    fn from_reader<R: io::Read>(mut reader: R) -> io::Result<Self> {
+    fn from_reader<R: io::Read>(mut reader: R) -> Result<Self, TgaError> {
         const HEADER_SIZE: usize = std::mem::size_of::<TgaHeader>();
         let mut buf = [0u8; HEADER_SIZE];
 
@@ -66,31 +103,137 @@ impl TgaHeader {
         unsafe { std::ptr::read(&self as *const Self as *const _) }
     }
 
-    fn validate(self) -> io::Result<Self> {
+    fn validate(self) -> Result<Self, TgaError> {
         if self.width <= 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "width is too low",
-            ));
+            return Err(TgaError::BadDimensions {
+                width: self.width,
+                height: self.height,
+            });
         }
 
         if self.height <= 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "height is too low",
-            ));
+            return Err(TgaError::BadDimensions {
+                width: self.width,
+                height: self.height,
+            });
         }
 
-        if let 8 | 24 | 32 = self.bits_per_pixel {
-        } else {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "unsupported bits per pixel",
-            ));
+        match self.color_map_type {
+            0 if self.is_color_mapped() => {
+                return Err(TgaError::BadColorMap("missing color map"));
+            }
+            0 => {}
+            1 => {
+                if let 15 | 16 | 24 | 32 = self.color_map_depth {
+                } else {
+                    return Err(TgaError::UnsupportedDepth(self.color_map_depth));
+                }
+            }
+            _ => {
+                return Err(TgaError::BadColorMap("unsupported color map type"));
+            }
+        }
+
+        let supported = match self.bits_per_pixel {
+            8 => true,
+            15 | 16 => self.data_type_code != 3 && self.data_type_code != 11,
+            24 | 32 => !self.is_color_mapped(),
+            _ => false,
+        };
+
+        if !supported {
+            return Err(TgaError::UnsupportedDepth(self.bits_per_pixel));
         }
 
         Ok(self)
     }
+
+    fn is_color_mapped(&self) -> bool {
+        matches!(self.data_type_code, 1 | 9)
+    }
+
+    fn is_rle(&self) -> bool {
+        self.data_type_code & 8 != 0
+    }
+
+    fn alpha_bits(&self) -> u8 {
+        self.image_descriptor & 0x0f
+    }
+
+    fn bytes_pp(&self) -> usize {
+        (self.bits_per_pixel as usize).div_ceil(8)
+    }
+
+    /// The width or height field of an image `size` pixels across, the format allows up to
+    /// 32767
+    fn dimension(size: usize) -> Result<i16, TgaError> {
+        if size > i16::MAX as usize {
+            return Err(TgaError::InvalidInput("image is too large for the format"));
+        }
+        Ok(size as i16)
+    }
+
+    fn read_image_id<R: io::Read>(&self, mut reader: R) -> Result<Vec<u8>, TgaError> {
+        let mut id = vec![0u8; self.id_len as usize];
+        reader.read_exact(&mut id)?;
+        Ok(id)
+    }
+
+    /// Reads the pixels as they are stored, decoding run length packets
+    fn read_pixels<R: io::Read>(&self, reader: R) -> Result<Vec<u8>, TgaError> {
+        let bytes_pp = self.bytes_pp();
+        let n_bytes = self.width as usize * self.height as usize * bytes_pp;
+
+        if self.is_rle() {
+            let mut data = Vec::new();
+            Image::load_rle_data(reader, bytes_pp, n_bytes, &mut data, &mut 0)?;
+            Ok(data)
+        } else {
+            // grows with the data read rather than trusting the header for a truncated file
+            let mut data = Vec::new();
+            reader.take(n_bytes as u64).read_to_end(&mut data)?;
+            if data.len() != n_bytes {
+                return Err(TgaError::Truncated);
+            }
+            Ok(data)
+        }
+    }
+
+    /// Reads as many pixels as possible, adding whatever went wrong to `warnings`
+    ///
+    /// ## returns
+    ///
+    /// - the pixels as they are stored, any that couldn't be read are left as 0
+    /// - the number of pixels that were read
+    fn read_pixels_lenient<R: io::Read>(
+        &self,
+        reader: R,
+        warnings: &mut Vec<TgaError>,
+    ) -> (Vec<u8>, usize) {
+        let bytes_pp = self.bytes_pp();
+        let n_bytes = self.width as usize * self.height as usize * bytes_pp;
+
+        let mut data = Vec::new();
+        let mut filled = 0;
+        let result = if self.is_rle() {
+            let result = Image::load_rle_data(reader, bytes_pp, n_bytes, &mut data, &mut filled);
+            data.resize(n_bytes, 0);
+            result
+        } else {
+            let result = reader.take(n_bytes as u64).read_to_end(&mut data);
+            filled = data.len();
+            data.resize(n_bytes, 0);
+            match result {
+                Ok(_) if filled != n_bytes => Err(TgaError::Truncated),
+                result => result.map(|_| ()).map_err(TgaError::from),
+            }
+        };
+
+        if let Err(error) = result {
+            warnings.push(error);
+        }
+        (data, filled / bytes_pp)
+    }
 }
 
 /// # The color of a single pixel
@@ -101,13 +244,24 @@ impl TgaHeader {
 /// - RGB
 /// - RGBA
 ///
-/// other formats will either result in an `io::ErrorKind::InvalidData` or have unexpected results
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// other formats will either result in a [`TgaError`] or have unexpected results
+///
+/// Colors are only equal if they have the same format and the same channels, a grey scale
+/// color is never equal to an RGB one.
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Color {
     bgra: [u8; 4],
     bytes_pp: usize,
 }
 
+impl PartialEq for Color {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Color {}
+
 impl Color {
     /// Creates a new grey scale color
     pub const fn grey_scale(v: u8) -> Self {
@@ -133,6 +287,45 @@ impl Color {
         }
     }
 
+    /// The red channel, or the brightness of a grey scale color
+    pub fn r(self) -> u8 {
+        match self.bytes_pp {
+            1 => self.bgra[0],
+            _ => self.bgra[2],
+        }
+    }
+
+    /// The green channel, or the brightness of a grey scale color
+    pub fn g(self) -> u8 {
+        match self.bytes_pp {
+            1 => self.bgra[0],
+            _ => self.bgra[1],
+        }
+    }
+
+    /// The blue channel, or the brightness of a grey scale color
+    pub fn b(self) -> u8 {
+        self.bgra[0]
+    }
+
+    /// The alpha channel, 255 if the color doesn't have one
+    pub fn a(self) -> u8 {
+        match self.bytes_pp {
+            4 => self.bgra[3],
+            _ => 255,
+        }
+    }
+
+    /// The brightness of the color with the default weights, see [`Color::luminance`]
+    pub fn luma(self) -> u8 {
+        self.luminance(LumaWeights::default())
+    }
+
+    /// The format of the color, None for the default color which has no channels
+    pub fn pixel_format(self) -> Option<PixelFormat> {
+        PixelFormat::from_bytes_pp(self.bytes_pp)
+    }
+
     const fn try_from_slice(slice: &[u8]) -> Option<Self> {
         match *slice {
             [v] => Some(Self::grey_scale(v)),
@@ -145,26 +338,253 @@ impl Color {
     fn as_slice(&self) -> &[u8] {
         &self.bgra[..self.bytes_pp]
     }
+
+    /// Expands a 16 bit A1R5G5B5 value, the attribute bit is only used as alpha if `alpha` is set
+    fn from_a1r5g5b5(value: u16, alpha: bool) -> Self {
+        let expand = |v: u16| {
+            let v = (v & 0x1f) as u8;
+            (v << 3) | (v >> 2)
+        };
+        let (r, g, b) = (expand(value >> 10), expand(value >> 5), expand(value));
+        if alpha {
+            Self::rgba(r, g, b, if value & 0x8000 != 0 { 255 } else { 0 })
+        } else {
+            Self::rgb(r, g, b)
+        }
+    }
+
+    /// Packs into a 16 bit A1R5G5B5 value, the attribute bit is set if the color is at least
+    /// half opaque
+    fn to_a1r5g5b5(self) -> u16 {
+        let [b, g, r, a] = self.to_rgba(255).bgra;
+        let attribute = if a >= 128 { 0x8000 } else { 0 };
+        attribute | (r as u16 >> 3) << 10 | (g as u16 >> 3) << 5 | b as u16 >> 3
+    }
 }
 
 /// The encoding style for saving an image
 #[derive(Debug, Clone)]
 pub enum Encoding {
     /// - Rle: run length encode the pixels, bad for natural images, good for
-    /// images with large areas of the same color
+    ///   images with large areas of the same color
     Rle,
+    /// - RleScanline: run length encode each row on its own so no packet continues past the end
+    ///   of a row, as TGA 2.0 recommends, and write a table of where each row starts, see
+    ///   [`Metadata::scan_line_table`]
+    RleScanline,
+    /// - Auto: run length encode the pixels only if that makes them smaller, the size of both is
+    ///   worked out before writing
+    Auto,
+}
+
+/// # Which corner of the screen the first pixel of an image is displayed in
+///
+/// The rest of the first row goes towards the opposite side, and the following rows go towards
+/// the opposite edge.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// - BottomLeft: rows go from bottom to top, this is the default of the format
+    #[default]
+    BottomLeft,
+    /// - BottomRight: rows go from bottom to top, and each row from right to left
+    BottomRight,
+    /// - TopLeft: rows go from top to bottom
+    TopLeft,
+    /// - TopRight: rows go from top to bottom, and each row from right to left
+    TopRight,
+}
+
+impl Orientation {
+    fn from_descriptor(image_descriptor: u8) -> Self {
+        match image_descriptor & 0x30 {
+            0x00 => Self::BottomLeft,
+            0x10 => Self::BottomRight,
+            0x20 => Self::TopLeft,
+            _ => Self::TopRight,
+        }
+    }
+
+    fn descriptor_bits(self) -> u8 {
+        match self {
+            Self::BottomLeft => 0x00,
+            Self::BottomRight => 0x10,
+            Self::TopLeft => 0x20,
+            Self::TopRight => 0x30,
+        }
+    }
+
+    fn is_top(self) -> bool {
+        self.descriptor_bits() & 0x20 != 0
+    }
+
+    fn is_right(self) -> bool {
+        self.descriptor_bits() & 0x10 != 0
+    }
+}
+
+/// # Options for reading an image
+///
+/// By default images are reoriented so the first row is the top and each row goes from left to
+/// right, whichever way they are stored.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    orientation: Option<Orientation>,
+    limits: DecodeLimits,
+    fill: Color,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            orientation: Some(Orientation::TopLeft),
+            limits: DecodeLimits::new(),
+            fill: Color::rgba(0, 0, 0, 0),
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Creates the default options, see [`ReadOptions`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the orientation to store the pixels in once read, `None` keeps the order they are
+    /// stored in the file and avoids flipping the image
+    pub fn orientation<O>(mut self, orientation: O) -> Self
+    where
+        O: Into<Option<Orientation>>,
+    {
+        self.orientation = orientation.into();
+        self
+    }
+
+    /// Sets the limits on the size of the image, see [`DecodeLimits`]
+    pub fn limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the color of the pixels that couldn't be read by a lenient read, it is converted to
+    /// the format of the image, by default this is transparent black, see
+    /// [`Image::from_reader_lenient`]
+    pub fn fill(mut self, color: Color) -> Self {
+        self.fill = color;
+        self
+    }
+}
+
+/// # Options for writing an image
+///
+/// An `Encoding` or `None` can be used wherever these are expected
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    encoding: Option<Encoding>,
+    bits_per_pixel: Option<u8>,
+    thumbnail: bool,
+    orientation: Option<Orientation>,
+}
+
+impl WriteOptions {
+    /// Creates the default options, uncompressed in the format of the image
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the encoding of the pixels
+    pub fn encoding<E>(mut self, encoding: E) -> Self
+    where
+        E: Into<Option<Encoding>>,
+    {
+        self.encoding = encoding.into();
+        self
+    }
+
+    /// Sets the number of bits per pixel to store, rather than the format of the image
+    ///
+    /// Only 15 and 16 are supported, these store 5 bits per color channel, with 16 bits
+    /// RGBA images keep 1 bit of alpha
+    pub fn bits_per_pixel(mut self, bits_per_pixel: u8) -> Self {
+        self.bits_per_pixel = Some(bits_per_pixel);
+        self
+    }
+
+    /// Sets whether to store a postage stamp of the image in the extension area, see
+    /// [`Image::thumbnail`]
+    ///
+    /// If the image has no metadata, empty metadata is written along with the postage stamp
+    pub fn thumbnail(mut self, thumbnail: bool) -> Self {
+        self.thumbnail = thumbnail;
+        self
+    }
+
+    /// Sets the order to write the pixels in, rather than the orientation of the image
+    pub fn orientation<O>(mut self, orientation: O) -> Self
+    where
+        O: Into<Option<Orientation>>,
+    {
+        self.orientation = orientation.into();
+        self
+    }
+}
+
+impl WriteOptions {
+    /// The header of a true color or grey scale image written with these options
+    fn header(
+        &self,
+        width: usize,
+        height: usize,
+        bytes_pp: usize,
+        orientation: Orientation,
+        origin: (u16, u16),
+    ) -> Result<TgaHeader, TgaError> {
+        let (bits_per_pixel, alpha_bits) = match self.bits_per_pixel {
+            None => (bytes_pp as u8 * 8, if bytes_pp == 4 { 8 } else { 0 }),
+            Some(bits @ 15) | Some(bits @ 16) => {
+                let alpha_bits = if bits == 16 && bytes_pp == 4 { 1 } else { 0 };
+                (bits, alpha_bits)
+            }
+            Some(bits) => return Err(TgaError::UnsupportedDepth(bits)),
+        };
+
+        Ok(TgaHeader {
+            data_type_code: Encoding::data_type_code(
+                &self.encoding,
+                (bits_per_pixel as usize).div_ceil(8),
+            ),
+            width: TgaHeader::dimension(width)?,
+            height: TgaHeader::dimension(height)?,
+            x_origin: origin.0 as i16,
+            y_origin: origin.1 as i16,
+            bits_per_pixel,
+            image_descriptor: alpha_bits | orientation.descriptor_bits(),
+            ..Default::default()
+        })
+    }
+}
+
+impl From<Encoding> for WriteOptions {
+    fn from(encoding: Encoding) -> Self {
+        Self::new().encoding(encoding)
+    }
+}
+
+impl From<Option<Encoding>> for WriteOptions {
+    fn from(encoding: Option<Encoding>) -> Self {
+        Self::new().encoding(encoding)
+    }
 }
 
 impl Encoding {
     fn data_type_code(encoding: &Option<Self>, bytes_pp: usize) -> u8 {
         match bytes_pp {
             1 => match encoding {
-                Some(Encoding::Rle) => 11,
-                _ => 3,
+                Some(_) => 11,
+                None => 3,
             },
             _ => match encoding {
-                Some(Encoding::Rle) => 10,
-                _ => 2,
+                Some(_) => 10,
+                None => 2,
             },
         }
     }
@@ -174,12 +594,21 @@ impl Encoding {
 ///
 /// This structure allows some basic image manipulation such as flipping vertically and
 /// horizontally, as well as single pixel manipulation.
+///
+/// The pixels are stored row by row in the image's [`Orientation`], new images are
+/// [`Orientation::BottomLeft`] like the format so `y` goes up the screen.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Image {
     width: usize,
     height: usize,
     bytes_pp: usize,
     data: Vec<u8>,
+    id: Vec<u8>,
+    metadata: Option<Metadata>,
+    developer_area: DeveloperArea,
+    orientation: Orientation,
+    origin: (u16, u16),
+    alpha_mode: AlphaMode,
 }
 
 impl Image {
@@ -192,94 +621,159 @@ impl Image {
             height,
             bytes_pp,
             data,
+            id: Vec::new(),
+            metadata: None,
+            developer_area: DeveloperArea::new(),
+            orientation: Orientation::BottomLeft,
+            origin: (0, 0),
+            alpha_mode: AlphaMode::Straight,
         }
     }
 
-    fn load_rle_data<R>(mut input: R, bytes_pp: usize, mut data: &mut [u8]) -> io::Result<()>
+    /// Decodes run length packets until `data` holds `n_bytes`, `filled` is the number of bytes
+    /// decoded even if reading fails part way
+    ///
+    /// `data` grows with the pixels decoded rather than trusting the header for a truncated
+    /// file, so it may be shorter than `n_bytes` if reading fails.
+    fn load_rle_data<R>(
+        mut input: R,
+        bytes_pp: usize,
+        n_bytes: usize,
+        data: &mut Vec<u8>,
+        filled: &mut usize,
+    ) -> Result<(), TgaError>
     where
         R: io::Read,
     {
-        let mut buf = [0u8; 4];
-        let buf = &mut buf[..bytes_pp];
-        let mut chunk_header = 0;
-        loop {
-            input.read_exact(std::slice::from_mut(&mut chunk_header))?;
-            let chunk_header = chunk_header;
-            if chunk_header < 128 {
-                for _ in 0..=chunk_header {
-                    input.read_exact(buf)?;
-                    data.write_all(buf)?;
-                }
-            } else {
-                input.read_exact(buf)?;
-                for _ in 0..(chunk_header - 127) {
-                    data.write_all(buf)?;
-                }
-            };
-
-            if data.is_empty() {
-                return Ok(());
-            }
+        const FIRST_PIXELS: usize = 1 << 16;
+        let mut packet = RlePacket::default();
+        while data.len() < n_bytes {
+            // a multiple of the pixel size so every pixel fits
+            let len = (data.len() * 2).max(FIRST_PIXELS * bytes_pp).min(n_bytes);
+            data.resize(len, 0);
+            packet.read_counted(&mut input, bytes_pp, data, filled)?;
         }
+        packet.finish()
     }
 
-    fn save_rle_data<W>(&self, mut output: W) -> io::Result<()>
+    fn save_rle_data<W>(data: &[u8], bytes_pp: usize, mut output: W) -> io::Result<()>
     where
         W: io::Write,
     {
-        const MAX_CHUNK_LENGTH: usize = 128;
-        let n_pixels = self.width * self.height;
-        let mut current_pixel = 0;
-
-        while current_pixel < n_pixels {
-            let chunk_start = current_pixel * self.bytes_pp;
-            let mut current_byte = current_pixel * self.bytes_pp;
-            let mut run_length = 1;
-            let mut raw = true;
-
-            while current_pixel + run_length < n_pixels && run_length < MAX_CHUNK_LENGTH {
-                let mut succ_eq = true;
-                let mut t = 0;
-                while succ_eq && t < self.bytes_pp {
-                    succ_eq =
-                        self.data[current_byte + t] == self.data[current_byte + t + self.bytes_pp];
-                    t += 1;
-                }
+        let mut packets = Vec::new();
+        for chunk in data.chunks(RLE_CHUNK * bytes_pp) {
+            packets.clear();
+            Self::push_rle_packets(chunk, bytes_pp, &mut packets);
+            Self::write_rle_packets(chunk, bytes_pp, &packets, &mut output)?;
+        }
+        Ok(())
+    }
 
-                current_byte += self.bytes_pp;
+    /// Writes the pixels of `data` as the packets with the header bytes `packets`
+    fn write_rle_packets<W>(
+        data: &[u8],
+        bytes_pp: usize,
+        packets: &[u8],
+        mut output: W,
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let mut start = 0;
+        for &header in packets {
+            let length = (header & 0x7f) as usize + 1;
+            output.write_all(&[header])?;
+            if header & 0x80 != 0 {
+                output.write_all(&data[start..][..bytes_pp])?;
+            } else {
+                output.write_all(&data[start..][..length * bytes_pp])?;
+            }
+            start += length * bytes_pp;
+        }
+        Ok(())
+    }
 
-                if run_length == 1 {
-                    raw = !succ_eq;
+    /// The number of bytes the packets with the header bytes `packets` are written in
+    fn rle_size(packets: &[u8], bytes_pp: usize) -> usize {
+        packets
+            .iter()
+            .map(|&header| {
+                let length = (header & 0x7f) as usize + 1;
+                1 + if header & 0x80 != 0 {
+                    bytes_pp
+                } else {
+                    length * bytes_pp
                 }
+            })
+            .sum()
+    }
 
-                if raw && succ_eq {
-                    run_length -= 1;
-                    break;
-                }
+    /// The header byte of each packet [`Image::save_rle_data`] writes, in order
+    fn rle_packets(data: &[u8], bytes_pp: usize) -> Vec<u8> {
+        let mut packets = Vec::new();
+        for chunk in data.chunks(RLE_CHUNK * bytes_pp) {
+            Self::push_rle_packets(chunk, bytes_pp, &mut packets);
+        }
+        packets
+    }
 
-                if !raw && !succ_eq {
-                    break;
-                }
+    /// Chooses the run length packets with the fewest bytes in total, by finding the cheapest
+    /// way to encode each prefix of the pixels from the cheaper prefixes before it
+    ///
+    /// The header byte of each packet is pushed onto `packets` in order, it holds the length
+    /// and whether it is a run.
+    fn push_rle_packets(data: &[u8], bytes_pp: usize, packets: &mut Vec<u8>) {
+        const MAX_PACKET_LENGTH: usize = 128;
+        let n_pixels = data.len() / bytes_pp;
+        let pixel = |i: usize| &data[i * bytes_pp..][..bytes_pp];
+
+        // cost[i] is the fewest bytes to encode the first i pixels, ending with the packet with
+        // the header last[i]
+        let mut cost = vec![0u32; n_pixels + 1];
+        let mut last = vec![0u8; n_pixels + 1];
+        // where the pixels equal to the current one start
+        let mut run_start = 0;
+        // where a raw packet ending at the current pixel can start, a raw packet from j to i costs
+        // cost[j] - j * bytes_pp + i * bytes_pp + 1 so the front always has the least of those
+        let mut raw_starts = VecDeque::<(usize, i64)>::new();
+
+        for i in 1..=n_pixels {
+            if i > 1 && pixel(i - 1) != pixel(i - 2) {
+                run_start = i - 1;
+            }
 
-                run_length += 1;
+            let key = cost[i - 1] as i64 - ((i - 1) * bytes_pp) as i64;
+            while raw_starts.back().is_some_and(|&(_, k)| k >= key) {
+                raw_starts.pop_back();
+            }
+            raw_starts.push_back((i - 1, key));
+            while raw_starts
+                .front()
+                .is_some_and(|&(j, _)| j + MAX_PACKET_LENGTH < i)
+            {
+                raw_starts.pop_front();
             }
 
-            current_pixel += run_length;
-            let buf = if raw {
-                [run_length as u8 - 1]
-            } else {
-                [run_length as u8 + 127]
-            };
+            let (j, _) = raw_starts[0];
+            cost[i] = cost[j] + 1 + ((i - j) * bytes_pp) as u32;
+            last[i] = (i - j - 1) as u8;
 
-            output.write_all(&buf)?;
-            if raw {
-                output.write_all(&self.data[chunk_start..][..(run_length * self.bytes_pp)])?;
-            } else {
-                output.write_all(&self.data[chunk_start..][..self.bytes_pp])?;
+            // a longer run always starts from a prefix that costs no more
+            let length = (i - run_start).min(MAX_PACKET_LENGTH);
+            let run_cost = cost[i - length] + 1 + bytes_pp as u32;
+            if length > 1 && run_cost < cost[i] {
+                cost[i] = run_cost;
+                last[i] = 0x80 | (length - 1) as u8;
             }
         }
 
-        Ok(())
+        let start = packets.len();
+        let mut i = n_pixels;
+        while i > 0 {
+            packets.push(last[i]);
+            i -= (last[i] & 0x7f) as usize + 1;
+        }
+        packets[start..].reverse();
     }
 
     /// # Reads an image from a file
@@ -288,13 +782,59 @@ impl Image {
     ///
     /// - while opening the file see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
     /// - while reading the file see [`Image::from_reader`]
-    pub fn read_tga_file<P>(filename: P) -> io::Result<Self>
+    pub fn read_tga_file<P>(filename: P) -> Result<Self, TgaError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::read_tga_file_with_options(filename, &ReadOptions::new())
+    }
+
+    /// # Reads an image from a buffer in memory, along with its metadata and developer area
+    ///
+    /// ## errors
+    ///
+    /// - see [`Image::from_reader`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TgaError> {
+        Self::from_seekable_reader(io::Cursor::new(bytes))
+    }
+
+    /// # Reads an image from a file with the given options
+    ///
+    /// ## errors
+    ///
+    /// - see [`Image::read_tga_file`]
+    pub fn read_tga_file_with_options<P>(
+        filename: P,
+        options: &ReadOptions,
+    ) -> Result<Self, TgaError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = std::fs::File::open(filename.as_ref())?;
+
+        Self::from_seekable_reader_with_options(io::BufReader::new(file), options)
+    }
+
+    /// # Reads as much of an image from a file as possible
+    ///
+    /// ## returns
+    ///
+    /// - the image and what went wrong reading it, see [`Image::from_seekable_reader_lenient`]
+    ///
+    /// ## errors
+    ///
+    /// - while opening the file see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
+    /// - see [`Image::from_reader_lenient`]
+    pub fn read_tga_file_lenient<P>(
+        filename: P,
+        options: &ReadOptions,
+    ) -> Result<(Self, Vec<TgaError>), TgaError>
     where
         P: AsRef<Path>,
     {
         let file = std::fs::File::open(filename.as_ref())?;
 
-        Self::from_reader(file)
+        Self::from_seekable_reader_lenient(io::BufReader::new(file), options)
     }
 
     /// # Writes an image to a file
@@ -302,91 +842,336 @@ impl Image {
     /// ## errors
     ///
     /// - while creating the file see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
-    pub fn write_tga_file<P, E>(&self, filename: P, encoding: E) -> io::Result<()>
+    /// - if the options ask for an unsupported format [`TgaError::UnsupportedDepth`] is returned
+    /// - if the width or height is over 32767 [`TgaError::InvalidInput`] is returned
+    pub fn write_tga_file<P, O>(&self, filename: P, options: O) -> Result<(), TgaError>
     where
         P: AsRef<Path>,
-        E: Into<Option<Encoding>>,
+        O: Into<WriteOptions>,
     {
-        let mut file = fs::File::create(filename.as_ref())?;
-        let encoding = encoding.into();
+        let file = fs::File::create(filename.as_ref())?;
+        self.write_to(io::BufWriter::new(file), options)
+    }
 
-        let data_type_code = Encoding::data_type_code(&encoding, self.bytes_pp);
+    /// # Writes an image to an `io::Write`
+    ///
+    /// The output isn't buffered, wrap it in an `io::BufWriter` if needed
+    ///
+    /// ## errors
+    ///
+    /// - any error from writing to `output`
+    /// - if the options ask for an unsupported format [`TgaError::UnsupportedDepth`] is returned
+    /// - if the width or height is over 32767 [`TgaError::InvalidInput`] is returned
+    pub fn write_to<W, O>(&self, output: W, options: O) -> Result<(), TgaError>
+    where
+        W: io::Write,
+        O: Into<WriteOptions>,
+    {
+        let options = options.into();
 
-        let header = TgaHeader {
-            data_type_code,
-            width: self.width as i16,
-            height: self.height as i16,
-            bits_per_pixel: self.bytes_pp as u8 * 8,
-            image_descriptor: 0x00,
-            ..Default::default()
+        if let Some(orientation) = options.orientation.filter(|&o| o != self.orientation) {
+            let mut image = self.clone();
+            image.reorient(orientation);
+            return image.write_to(output, options);
+        }
+
+        let thumbnail = if options.thumbnail {
+            Some(self.thumbnail())
+        } else {
+            None
         };
 
-        let header = header.into_buffer();
+        let header = options.header(
+            self.width,
+            self.height,
+            self.bytes_pp,
+            self.orientation,
+            self.origin,
+        )?;
+
+        let current = self.metadata.as_ref().map(Metadata::attributes_type);
+        let attributes_type = self
+            .alpha_mode
+            .attributes_type(header.alpha_bits() != 0, current.unwrap_or_default());
+
+        let (data, postage_stamp) = if header.bytes_pp() == 2 {
+            (
+                Cow::Owned(Self::to_a1r5g5b5_data(&self.data, self.bytes_pp)),
+                thumbnail.map(|thumbnail| {
+                    let data = Self::to_a1r5g5b5_data(&thumbnail.data, thumbnail.bytes_pp);
+                    thumbnail.postage_stamp(data)
+                }),
+            )
+        } else {
+            (
+                Cow::Borrowed(&self.data[..]),
+                thumbnail.map(|thumbnail| thumbnail.postage_stamp(thumbnail.data.clone())),
+            )
+        };
 
-        file.write_all(&header)?;
+        let tga = TgaFile {
+            header,
+            id: &self.id,
+            color_map: &[],
+            data: &data,
+            encoding: &options.encoding,
+            metadata: self.metadata.as_ref(),
+            attributes_type: Some(attributes_type),
+            developer_area: Some(&self.developer_area),
+            postage_stamp: postage_stamp.as_deref(),
+        };
 
-        match encoding {
-            None => file.write_all(&self.data)?,
-            Some(Encoding::Rle) => self.save_rle_data(&mut file)?,
-        }
+        tga.write(output)
+    }
 
-        let developer_area_ref = [0, 0, 0, 0];
-        let extension_area_ref = [0, 0, 0, 0];
-        let footer = b"TRUEVISION-XFILE.\0";
+    /// # Writes an image to a buffer in memory
+    ///
+    /// ## errors
+    ///
+    /// - see [`Image::write_to`]
+    pub fn to_bytes<O>(&self, options: O) -> Result<Vec<u8>, TgaError>
+    where
+        O: Into<WriteOptions>,
+    {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes, options)?;
+        Ok(bytes)
+    }
 
-        file.write_all(&developer_area_ref)?;
-        file.write_all(&extension_area_ref)?;
-        file.write_all(footer)?;
+    /// Prefixes the pixels of a thumbnail with its size, as it is stored in a file
+    fn postage_stamp(&self, mut data: Vec<u8>) -> Vec<u8> {
+        data.splice(0..0, [self.width as u8, self.height as u8].iter().copied());
+        data
+    }
 
-        Ok(())
+    /// # Scales the image down to fit in 64x64 pixels, keeping its aspect ratio
+    ///
+    /// Each pixel is the average of the pixels it covers, images that already fit are copied.
+    /// The thumbnail has the same format and orientation, and no ID, metadata or developer area.
+    pub fn thumbnail(&self) -> Image {
+        const MAX_SIZE: usize = 64;
+
+        let largest = self.width.max(self.height);
+        let (width, height) = if largest <= MAX_SIZE {
+            (self.width, self.height)
+        } else {
+            (
+                (self.width * MAX_SIZE / largest).max(1),
+                (self.height * MAX_SIZE / largest).max(1),
+            )
+        };
+
+        let mut thumbnail = Image::new(width, height, self.bytes_pp);
+        thumbnail.orientation = self.orientation;
+        for y in 0..height {
+            let rows = y * self.height / height..(y + 1) * self.height / height;
+            for x in 0..width {
+                let columns = x * self.width / width..(x + 1) * self.width / width;
+                let mut sum = [0usize; 4];
+                for row in rows.clone() {
+                    let start = (row * self.width + columns.start) * self.bytes_pp;
+                    let end = (row * self.width + columns.end) * self.bytes_pp;
+                    for pixel in self.data[start..end].chunks_exact(self.bytes_pp) {
+                        for (sum, &value) in sum.iter_mut().zip(pixel) {
+                            *sum += value as usize;
+                        }
+                    }
+                }
+
+                let count = rows.len() * columns.len();
+                let start = (y * width + x) * self.bytes_pp;
+                let pixel = &mut thumbnail.data[start..start + self.bytes_pp];
+                for (value, &sum) in pixel.iter_mut().zip(&sum) {
+                    *value = ((sum + count / 2) / count) as u8;
+                }
+            }
+        }
+        thumbnail
+    }
+
+    fn to_a1r5g5b5_data(data: &[u8], bytes_pp: usize) -> Vec<u8> {
+        data.chunks_exact(bytes_pp)
+            .filter_map(Color::try_from_slice)
+            .flat_map(|color| color.to_a1r5g5b5().to_le_bytes())
+            .collect()
+    }
+
+    fn from_a1r5g5b5_data(data: &[u8], alpha: bool) -> Vec<u8> {
+        let mut result = Vec::with_capacity(data.len() * 2);
+        for pixel in data.chunks_exact(2) {
+            let color = Color::from_a1r5g5b5(u16::from_le_bytes([pixel[0], pixel[1]]), alpha);
+            result.extend_from_slice(color.as_slice());
+        }
+        result
     }
 
     /// Turns the image into its mirror image along the horizontal axis,
     /// this occurs in place
     pub fn flip_horizontally(&mut self) {
-        let half = self.width / 2;
-        for i in 0..half {
-            for j in 0..self.height {
-                let c1 = self.get(i, j).unwrap();
-                let c2 = self.get(self.width - 1 - i, j).unwrap();
-                self.set(i, j, c2);
-                self.set(self.width - 1 - i, j, c1);
+        let bytes_pp = self.bytes_pp;
+        if self.width == 0 {
+            return;
+        }
+        for row in self.data.chunks_exact_mut(self.width * bytes_pp) {
+            // reversing the bytes reverses the order of the pixels and the bytes in each pixel
+            row.reverse();
+            for pixel in row.chunks_exact_mut(bytes_pp) {
+                pixel.reverse();
             }
         }
     }
 
     /// Turns the image into its mirror image along the vertical axis.
     pub fn flip_vertically(&mut self) {
-        let half = self.height / 2;
-        for i in 0..self.width {
-            for j in 0..half {
-                let c1 = self.get(i, j).unwrap();
-                let c2 = self.get(i, self.height - 1 - j).unwrap();
-                self.set(i, j, c2);
-                self.set(i, self.height - 1 - j, c1);
-            }
+        let row_size = self.width * self.bytes_pp;
+        for j in 0..self.height / 2 {
+            let (top, bottom) = self.data.split_at_mut((self.height - 1 - j) * row_size);
+            top[j * row_size..][..row_size].swap_with_slice(&mut bottom[..row_size]);
         }
     }
 
-    /// Access a single pixel within the image
-    ///
-    /// ## returns
-    ///
-    /// - Some(Color) if the indicies are inside the image area
-    /// - None otherwise
-    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
-        if x >= self.width || y >= self.height {
-            return None;
+    /// Turns the image a quarter turn clockwise as it is displayed, swapping the width and height
+    pub fn rotate90(&mut self) {
+        if self.is_mirrored() {
+            self.data = self.transposed(true, false);
+        } else {
+            self.data = self.transposed(false, true);
         }
-        Color::try_from_slice(&self.data[((x + y * self.width) * self.bytes_pp)..][..self.bytes_pp])
+        std::mem::swap(&mut self.width, &mut self.height);
     }
 
-    /// Sets a single pixel to a color
-    ///
-    /// ## returns
-    ///
-    /// - true if this color matches the format of this image and is inside the image
-    /// - false otherwise
+    /// Turns the image a half turn, this occurs in place
+    pub fn rotate180(&mut self) {
+        // reversing the bytes reverses the order of the pixels and the bytes in each pixel
+        self.data.reverse();
+        for pixel in self.data.chunks_exact_mut(self.bytes_pp) {
+            pixel.reverse();
+        }
+    }
+
+    /// Turns the image a quarter turn anticlockwise as it is displayed, swapping the width and
+    /// height
+    pub fn rotate270(&mut self) {
+        if self.is_mirrored() {
+            self.data = self.transposed(false, true);
+        } else {
+            self.data = self.transposed(true, false);
+        }
+        std::mem::swap(&mut self.width, &mut self.height);
+    }
+
+    /// Mirrors the image along the diagonal from the top left corner as it is displayed,
+    /// swapping the width and height
+    pub fn transpose(&mut self) {
+        let mirrored = self.is_mirrored();
+        self.data = self.transposed(mirrored, mirrored);
+        std::mem::swap(&mut self.width, &mut self.height);
+    }
+
+    /// Is true if the pixels are stored flipped one way from how they are displayed, so turning
+    /// the stored pixels clockwise turns the displayed image anticlockwise
+    fn is_mirrored(&self) -> bool {
+        self.orientation.is_top() == self.orientation.is_right()
+    }
+
+    /// The stored pixels with the rows and columns swapped, the first column becomes the first
+    /// row, `reverse_x` reverses the order of the rows and `reverse_y` the pixels in each row
+    fn transposed(&self, reverse_x: bool, reverse_y: bool) -> Vec<u8> {
+        match self.bytes_pp {
+            1 => self.transposed_pixels::<1>(reverse_x, reverse_y),
+            3 => self.transposed_pixels::<3>(reverse_x, reverse_y),
+            4 => self.transposed_pixels::<4>(reverse_x, reverse_y),
+            _ => self.transposed_slices(reverse_x, reverse_y),
+        }
+    }
+
+    /// [`Image::transposed`] for any other number of bytes per pixel, a pixel at a time
+    fn transposed_slices(&self, reverse_x: bool, reverse_y: bool) -> Vec<u8> {
+        let (width, height, bytes_pp) = (self.width, self.height, self.bytes_pp);
+        let mut data = vec![0u8; self.data.len()];
+
+        for y in 0..height {
+            let new_x = if reverse_y { height - 1 - y } else { y };
+            for x in 0..width {
+                let new_y = if reverse_x { width - 1 - x } else { x };
+                let i = (new_x + new_y * height) * bytes_pp;
+                data[i..i + bytes_pp]
+                    .copy_from_slice(&self.data[(x + y * width) * bytes_pp..][..bytes_pp]);
+            }
+        }
+        data
+    }
+
+    /// [`Image::transposed`] for `N` bytes per pixel, so each pixel is a fixed size copy
+    fn transposed_pixels<const N: usize>(&self, reverse_x: bool, reverse_y: bool) -> Vec<u8> {
+        // copying a tile at a time keeps the rows being read and written in the cache
+        const TILE: usize = 32;
+        let (width, height) = (self.width, self.height);
+        let mut data = vec![0u8; self.data.len()];
+
+        for tile_y in (0..height).step_by(TILE) {
+            for tile_x in (0..width).step_by(TILE) {
+                for y in tile_y..(tile_y + TILE).min(height) {
+                    let new_x = if reverse_y { height - 1 - y } else { y };
+                    let row = &self.data[(tile_x + y * width) * N..];
+                    let pixels = row.chunks_exact(N).take(TILE.min(width - tile_x));
+                    for (x, pixel) in (tile_x..).zip(pixels) {
+                        let new_y = if reverse_x { width - 1 - x } else { x };
+                        let i = (new_x + new_y * height) * N;
+                        data[i..i + N].copy_from_slice(pixel);
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    /// The order the pixels are stored in
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Flips the image as needed so the pixels are stored in `orientation`, this doesn't change
+    /// how the image is displayed
+    pub fn reorient(&mut self, orientation: Orientation) {
+        if self.orientation.is_right() != orientation.is_right() {
+            self.flip_horizontally();
+        }
+        if self.orientation.is_top() != orientation.is_top() {
+            self.flip_vertically();
+        }
+        self.orientation = orientation;
+    }
+
+    /// The position on screen of the image's lower left corner, as stored in the header
+    pub fn origin(&self) -> (u16, u16) {
+        self.origin
+    }
+
+    /// Sets the position on screen of the image's lower left corner
+    pub fn set_origin(&mut self, x: u16, y: u16) {
+        self.origin = (x, y);
+    }
+
+    /// Access a single pixel within the image
+    ///
+    /// ## returns
+    ///
+    /// - Some(Color) if the indicies are inside the image area
+    /// - None otherwise
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Color::try_from_slice(&self.data[((x + y * self.width) * self.bytes_pp)..][..self.bytes_pp])
+    }
+
+    /// Sets a single pixel to a color
+    ///
+    /// ## returns
+    ///
+    /// - true if this color matches the format of this image and is inside the image
+    /// - false otherwise, see [`Color::convert`] to match the format
     ///
     pub fn set(&mut self, x: usize, y: usize, color: Color) -> bool {
         if x >= self.width || y >= self.height {
@@ -429,68 +1214,1016 @@ impl Image {
         self.data.as_mut_slice()
     }
 
+    /// The image ID field, this is usually text describing the image but may be any data
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Sets the image ID field
+    ///
+    /// ## returns
+    ///
+    /// - true if the ID fits in the 255 bytes of the field
+    /// - false otherwise, the ID is left unchanged
+    pub fn set_id<I>(&mut self, id: I) -> bool
+    where
+        I: AsRef<[u8]>,
+    {
+        let id = id.as_ref();
+        if id.len() > u8::MAX as usize {
+            return false;
+        }
+        self.id = id.to_vec();
+        true
+    }
+
+    /// The metadata from the extension area of the file this image was read from
+    ///
+    /// This is only read by [`Image::from_seekable_reader`] and [`Image::read_tga_file`]
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Sets the metadata to write in the extension area, `None` doesn't write one
+    pub fn set_metadata<M>(&mut self, metadata: M)
+    where
+        M: Into<Option<Metadata>>,
+    {
+        self.metadata = metadata.into();
+    }
+
+    /// The tagged fields from the developer area of the file this image was read from, these are
+    /// written back when the image is saved
+    ///
+    /// This is only read by [`Image::from_seekable_reader`] and [`Image::read_tga_file`]
+    pub fn developer_area(&self) -> &DeveloperArea {
+        &self.developer_area
+    }
+
+    /// The developer area to add, change or remove tagged fields, an empty developer area isn't
+    /// written
+    pub fn developer_area_mut(&mut self) -> &mut DeveloperArea {
+        &mut self.developer_area
+    }
+
     /// Sets the data of this image to all 0s, this is usually black, or fully transparent black
     pub fn clear(&mut self) {
         self.data.iter_mut().for_each(|b| *b = 0);
     }
 
+    /// Reads an image from an `io::Read` that can also seek, this also reads the metadata in the
+    /// extension area and the fields of the developer area at the end of the file
+    ///
+    /// The image is expected to start at the current position, afterwards the reader is left in
+    /// an unspecified position. A metadata or developer area that can't be read, or is larger
+    /// than the [`DecodeLimits`] allow, is left empty, see [`Image::from_seekable_reader_lenient`]
+    /// to find out why.
+    ///
+    /// ## Errors
+    ///
+    /// - see [`Image::from_reader`]
+    pub fn from_seekable_reader<R>(reader: R) -> Result<Self, TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        Self::from_seekable_reader_with_options(reader, &ReadOptions::new())
+    }
+
+    /// Reads an image from an `io::Read` that can also seek with the given options, see
+    /// [`Image::from_seekable_reader`]
+    pub fn from_seekable_reader_with_options<R>(
+        mut reader: R,
+        options: &ReadOptions,
+    ) -> Result<Self, TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        let start = reader.stream_position()?;
+        let mut image = Self::from_reader_with_options(&mut reader, options)?;
+
+        // the pixels are fine even if what follows them isn't
+        let footer = Footer::from_reader(&mut reader, start)
+            .unwrap_or_default()
+            .unwrap_or_default();
+        image.metadata = Metadata::from_seekable_reader(
+            &mut reader,
+            start,
+            footer.extension_offset,
+            image.height,
+            &options.limits,
+        )
+        .unwrap_or_default();
+        image.read_alpha_mode();
+        image.developer_area = DeveloperArea::from_seekable_reader(
+            &mut reader,
+            start,
+            footer.developer_offset,
+            &options.limits,
+        )
+        .unwrap_or_default();
+        Ok(image)
+    }
+
+    /// Reads as much of an image as possible from an `io::Read` that can also seek, see
+    /// [`Image::from_reader_lenient`]
+    ///
+    /// A metadata or developer area that can't be read is also a warning, and is left empty.
+    pub fn from_seekable_reader_lenient<R>(
+        mut reader: R,
+        options: &ReadOptions,
+    ) -> Result<(Self, Vec<TgaError>), TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        let start = reader.stream_position()?;
+        let (mut image, mut warnings) = Self::from_reader_lenient(&mut reader, options)?;
+
+        let footer = match Footer::from_reader(&mut reader, start) {
+            Ok(footer) => footer.unwrap_or_default(),
+            Err(error) => {
+                warnings.push(error);
+                return Ok((image, warnings));
+            }
+        };
+        match Metadata::from_seekable_reader(
+            &mut reader,
+            start,
+            footer.extension_offset,
+            image.height,
+            &options.limits,
+        ) {
+            Ok(metadata) => {
+                image.metadata = metadata;
+                image.read_alpha_mode();
+            }
+            Err(error) => warnings.push(error),
+        }
+        match DeveloperArea::from_seekable_reader(
+            &mut reader,
+            start,
+            footer.developer_offset,
+            &options.limits,
+        ) {
+            Ok(developer_area) => image.developer_area = developer_area,
+            Err(error) => warnings.push(error),
+        }
+        Ok((image, warnings))
+    }
+
+    /// Uses the attributes type of the metadata as the alpha mode, if it has one
+    fn read_alpha_mode(&mut self) {
+        let attributes_type = self.metadata.as_ref().map(Metadata::attributes_type);
+        if let Some(alpha_mode) = attributes_type.and_then(AlphaMode::from_attributes_type) {
+            self.alpha_mode = alpha_mode;
+        }
+    }
+
+    /// Reads only the postage stamp of an image file, without decoding the image
+    ///
+    /// ## returns
+    ///
+    /// - None if the file has no postage stamp
+    ///
+    /// ## Errors
+    ///
+    /// - see [`Image::thumbnail_from_seekable_reader`]
+    pub fn read_thumbnail<P>(filename: P) -> Result<Option<Self>, TgaError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = fs::File::open(filename.as_ref())?;
+        Self::thumbnail_from_seekable_reader(io::BufReader::new(file))
+    }
+
+    /// Reads only the postage stamp of an image from an `io::Read` that can also seek, it is
+    /// oriented and expanded to true color like the image would be
+    ///
+    /// The image is expected to start at the current position, afterwards the reader is left in
+    /// an unspecified position
+    ///
+    /// ## returns
+    ///
+    /// - None if the image has no postage stamp
+    ///
+    /// ## Errors
+    ///
+    /// - see [`Image::from_reader`]
+    pub fn thumbnail_from_seekable_reader<R>(mut reader: R) -> Result<Option<Self>, TgaError>
+    where
+        R: io::Read + io::Seek,
+    {
+        let start = reader.stream_position()?;
+        let header = TgaHeader::from_reader(&mut reader)?;
+        header.read_image_id(&mut reader)?;
+        let color_map = ColorMap::from_reader(&mut reader, &header)?;
+
+        let footer = Footer::from_reader(&mut reader, start)?.unwrap_or_default();
+        let offset = match Metadata::from_seekable_reader(
+            &mut reader,
+            start,
+            footer.extension_offset,
+            header.height as usize,
+            &DecodeLimits::new(),
+        )? {
+            Some(metadata) if metadata.postage_stamp_offset != 0 => metadata.postage_stamp_offset,
+            _ => return Ok(None),
+        };
+
+        let mut size = [0u8; 2];
+        reader.seek(io::SeekFrom::Start(start + offset as u64))?;
+        reader.read_exact(&mut size)?;
+        if size[0] == 0 || size[1] == 0 {
+            return Ok(None);
+        }
+
+        // the postage stamp is never compressed
+        let header = TgaHeader {
+            data_type_code: header.data_type_code & !8,
+            width: size[0] as i16,
+            height: size[1] as i16,
+            ..header
+        };
+        let mut thumbnail = Self::from_parts(reader, &header, Vec::new(), color_map)?;
+        thumbnail.reorient(Orientation::TopLeft);
+        thumbnail.origin = (0, 0);
+        Ok(Some(thumbnail))
+    }
+
     /// Reads an image from an `io::Read`
     ///
     /// ## Errors
     ///
-    /// - see [<https://doc.rust-lang.org/std/fs/struct.File.html#errors>]
-    /// - if the format isn't supported and `io::Error` of kind `io::ErrorKind::InvalidData`
-    ///  is returned
-    pub fn from_reader<R>(mut reader: R) -> Result<Self, io::Error>
+    /// - [`TgaError::Io`] if reading fails, or [`TgaError::Truncated`] if the data ends early
+    /// - if the header, color map or pixels are invalid or not supported, the matching
+    ///   [`TgaError`] is returned
+    pub fn from_reader<R>(reader: R) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        Self::from_reader_with_options(reader, &ReadOptions::new())
+    }
+
+    /// Reads an image from an `io::Read` with the given options, see [`Image::from_reader`]
+    pub fn from_reader_with_options<R>(
+        mut reader: R,
+        options: &ReadOptions,
+    ) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        let header = TgaHeader::from_reader(&mut reader)?;
+        options.limits.check(&header)?;
+        let id = header.read_image_id(&mut reader)?;
+        let color_map = ColorMap::from_reader(&mut reader, &header)?;
+        let mut image = Self::from_parts(reader, &header, id, color_map)?;
+        if let Some(orientation) = options.orientation {
+            image.reorient(orientation);
+        }
+        Ok(image)
+    }
+
+    /// Reads as much of an image from an `io::Read` as possible, for recovering damaged files
+    ///
+    /// Pixels that couldn't be read, because the data is truncated or a packet or color map
+    /// index is corrupt, are set to the fill color of the options, see [`ReadOptions::fill`].
+    /// Everything before the pixels is still needed so errors there aren't recovered from.
+    ///
+    /// ## returns
+    ///
+    /// - the image and a warning for each problem found, an empty list if nothing went wrong
+    ///
+    /// ## Errors
+    ///
+    /// - if the header, image ID or color map can't be read the matching [`TgaError`] is
+    ///   returned, see [`Image::from_reader`]
+    pub fn from_reader_lenient<R>(
+        mut reader: R,
+        options: &ReadOptions,
+    ) -> Result<(Self, Vec<TgaError>), TgaError>
     where
         R: io::Read,
     {
         let header = TgaHeader::from_reader(&mut reader)?;
+        options.limits.check(&header)?;
+        let id = header.read_image_id(&mut reader)?;
+        let color_map = ColorMap::from_reader(&mut reader, &header)?;
+
+        let mut warnings = Vec::new();
+        let mut image =
+            Self::from_parts_lenient(reader, &header, id, color_map, options.fill, &mut warnings)?;
+        if let Some(orientation) = options.orientation {
+            image.reorient(orientation);
+        }
+        Ok((image, warnings))
+    }
 
-        let width = header.width as usize;
-        let height = header.height as usize;
-        let bytes_pp = header.bits_per_pixel as usize / 8;
-        let n_bytes = bytes_pp * width * height;
-        let data = match header.data_type_code {
-            3 | 2 => {
-                let mut data = vec![0u8; n_bytes];
-                reader.read_exact(&mut data)?;
-                data
+    /// Reads the pixels that follow the color map, in the order they are stored
+    fn from_parts<R>(
+        reader: R,
+        header: &TgaHeader,
+        id: Vec<u8>,
+        color_map: Option<ColorMap>,
+    ) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        if let Some(color_map) = color_map {
+            if header.is_color_mapped() {
+                let mut image = IndexedImage::from_parts(reader, header, id, color_map)?.to_image();
+                image.origin = (header.x_origin as u16, header.y_origin as u16);
+                return Ok(image);
             }
-            11 | 10 => {
-                let mut data = vec![0u8; n_bytes];
-                Self::load_rle_data(&mut reader, bytes_pp, &mut data)?;
-                data
+        }
+
+        let data = match header.data_type_code {
+            3 | 2 | 11 | 10 => header.read_pixels(reader)?,
+            _ => return Err(TgaError::UnsupportedDataType(header.data_type_code)),
+        };
+        Ok(Self::from_data(header, id, data))
+    }
+
+    /// Reads as much of the pixels that follow the color map as possible, in the order they are
+    /// stored, see [`Image::from_reader_lenient`]
+    fn from_parts_lenient<R>(
+        reader: R,
+        header: &TgaHeader,
+        id: Vec<u8>,
+        color_map: Option<ColorMap>,
+        fill: Color,
+        warnings: &mut Vec<TgaError>,
+    ) -> Result<Self, TgaError>
+    where
+        R: io::Read,
+    {
+        if !matches!(header.data_type_code, 1 | 2 | 3 | 9 | 10 | 11) {
+            return Err(TgaError::UnsupportedDataType(header.data_type_code));
+        }
+
+        let (data, read) = header.read_pixels_lenient(reader, warnings);
+        let (mut image, valid) = match color_map.filter(|_| header.is_color_mapped()) {
+            Some(color_map) => {
+                let indexed = IndexedImage::from_data(header, id, color_map, &data);
+                let valid = indexed.indices()[..read]
+                    .iter()
+                    .map(|&index| indexed.color_map().get(index).is_some())
+                    .collect::<Vec<_>>();
+                if valid.contains(&false) {
+                    warnings.push(TgaError::BadColorMap("color map index out of range"));
+                }
+                (indexed.to_image(), valid)
             }
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("unknown file format {:x}", header.data_type_code),
-                ))
+            None => (Self::from_data(header, id, data), vec![true; read]),
+        };
+
+        let format = PixelFormat::from_bytes_pp(image.bytes_pp)
+            .expect("images are read as grey, RGB or RGBA");
+        let fill = fill.convert(format, &ConvertOptions::new());
+        let bytes_pp = image.bytes_pp;
+        for (i, pixel) in image.data.chunks_exact_mut(bytes_pp).enumerate() {
+            if !valid.get(i).copied().unwrap_or(false) {
+                pixel.copy_from_slice(fill.as_slice());
             }
+        }
+        image.origin = (header.x_origin as u16, header.y_origin as u16);
+        Ok(image)
+    }
+
+    /// Builds an image from true color or grey scale pixels as they are stored
+    fn from_data(header: &TgaHeader, id: Vec<u8>, data: Vec<u8>) -> Self {
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let bytes_pp = header.bytes_pp();
+        let (bytes_pp, data) = if bytes_pp == 2 {
+            let alpha = header.bits_per_pixel == 16 && header.alpha_bits() != 0;
+            (
+                if alpha { 4 } else { 3 },
+                Self::from_a1r5g5b5_data(&data, alpha),
+            )
+        } else {
+            (bytes_pp, data)
         };
-        let mut result = Self {
+        Self {
             width,
             height,
             bytes_pp,
             data,
+            id,
+            metadata: None,
+            developer_area: DeveloperArea::new(),
+            orientation: Orientation::from_descriptor(header.image_descriptor),
+            origin: (header.x_origin as u16, header.y_origin as u16),
+            alpha_mode: AlphaMode::from_alpha_bits(header.alpha_bits(), bytes_pp),
+        }
+    }
+}
+
+/// Everything that is written to a TGA file, `data` holds the pixels as they are stored in
+/// the file
+struct TgaFile<'a> {
+    header: TgaHeader,
+    id: &'a [u8],
+    color_map: &'a [u8],
+    data: &'a [u8],
+    encoding: &'a Option<Encoding>,
+    metadata: Option<&'a Metadata>,
+    /// Replaces the attributes type of the metadata, if it is needed a metadata is written
+    attributes_type: Option<AttributesType>,
+    developer_area: Option<&'a DeveloperArea>,
+    postage_stamp: Option<&'a [u8]>,
+}
+
+impl TgaFile<'_> {
+    fn write<W>(&self, output: W) -> Result<(), TgaError>
+    where
+        W: io::Write,
+    {
+        let bytes_pp = self.header.bytes_pp();
+        if let Some(Encoding::Auto) = self.encoding {
+            let packets = Image::rle_packets(self.data, bytes_pp);
+            let rle = Image::rle_size(&packets, bytes_pp) < self.data.len();
+            let (data_type_code, encoding) = if rle {
+                (self.header.data_type_code | 8, &Some(Encoding::Rle))
+            } else {
+                (self.header.data_type_code & !8, &None)
+            };
+            let file = TgaFile {
+                header: TgaHeader {
+                    data_type_code,
+                    ..self.header
+                },
+                encoding,
+                ..*self
+            };
+            return file.write_packets(output, Some(&packets).filter(|_| rle));
+        }
+        self.write_packets(output, None)
+    }
+
+    /// Writes the file, `packets` are the run length packets of the pixels if they are already
+    /// known, see [`Image::rle_packets`]
+    fn write_packets<W>(&self, output: W, packets: Option<&Vec<u8>>) -> Result<(), TgaError>
+    where
+        W: io::Write,
+    {
+        let bytes_pp = self.header.bytes_pp();
+
+        let mut output = CountingWriter::new(output);
+
+        self.write_start(&mut output)?;
+
+        let mut scan_lines = Vec::new();
+        match self.encoding {
+            None => output.write_all(self.data)?,
+            Some(Encoding::Rle) => match packets {
+                Some(packets) => {
+                    Image::write_rle_packets(self.data, bytes_pp, packets, &mut output)?
+                }
+                None => Image::save_rle_data(self.data, bytes_pp, &mut output)?,
+            },
+            Some(Encoding::RleScanline) => {
+                let row_size = self.header.width as usize * bytes_pp;
+                for y in 0..self.header.height as usize {
+                    scan_lines.push(output.position()?);
+                    let row = &self.data[y * row_size..][..row_size];
+                    Image::save_rle_data(row, bytes_pp, &mut output)?;
+                }
+            }
+            Some(Encoding::Auto) => unreachable!("automatic encoding is chosen before writing"),
+        }
+
+        self.write_end(&mut output, &scan_lines)?;
+
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Writes everything before the pixels
+    fn write_start<W>(&self, mut output: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let header = TgaHeader {
+            id_len: self.id.len() as u8,
+            ..self.header
         };
-        if (header.image_descriptor & 0x20) == 0 {
-            result.flip_vertically();
+
+        output.write_all(&header.into_buffer())?;
+        output.write_all(self.id)?;
+        output.write_all(self.color_map)
+    }
+
+    /// Writes everything after the pixels, `scan_lines` is the offset of each row if a scan line
+    /// table should be written
+    fn write_end<W>(
+        &self,
+        output: &mut CountingWriter<W>,
+        scan_lines: &[u32],
+    ) -> Result<(), TgaError>
+    where
+        W: io::Write,
+    {
+        let mut footer = Footer::default();
+
+        if let Some(developer_area) = self.developer_area.filter(|area| !area.is_empty()) {
+            let position = output.position()?;
+            footer.developer_offset = developer_area.write(&mut *output, position)?;
         }
-        if (header.image_descriptor & 0x10) != 0 {
-            result.flip_horizontally();
+
+        let mut metadata = match self.metadata {
+            Some(metadata) => Some(metadata.clone()),
+            None if self.postage_stamp.is_some()
+                || !scan_lines.is_empty()
+                || matches!(
+                    self.attributes_type,
+                    Some(
+                        AttributesType::UndefinedIgnore
+                            | AttributesType::UndefinedRetain
+                            | AttributesType::PremultipliedAlpha
+                    )
+                ) =>
+            {
+                Some(Metadata::builder().build())
+            }
+            None => None,
+        };
+
+        if let Some(metadata) = &mut metadata {
+            // offsets read from another file don't apply to this one
+            metadata.color_correction_offset = 0;
+            metadata.postage_stamp_offset = 0;
+            metadata.scan_line_offset = 0;
+            if let Some(attributes_type) = self.attributes_type {
+                metadata.attributes_type = attributes_type;
+            }
+
+            if let Some(postage_stamp) = self.postage_stamp {
+                metadata.postage_stamp_offset = output.position()?;
+                output.write_all(postage_stamp)?;
+            }
+
+            if !scan_lines.is_empty() {
+                metadata.scan_line_offset = output.position()?;
+                for offset in scan_lines {
+                    output.write_all(&offset.to_le_bytes())?;
+                }
+            }
+
+            footer.extension_offset = output.position()?;
+            output.write_all(&metadata.to_buffer())?;
         }
-        Ok(result)
+
+        footer.write(output)?;
+        Ok(())
+    }
+}
+
+/// Keeps track of the number of bytes written, for the offsets in the footer
+#[derive(Debug)]
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn position(&self) -> Result<u32, TgaError> {
+        use std::convert::TryFrom;
+
+        u32::try_from(self.count)
+            .map_err(|_| TgaError::InvalidInput("image is too large for a footer"))
+    }
+}
+
+impl<W: io::Write> io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
     #[test]
     fn header_size() {
-        let size = std::mem::size_of::<super::TgaHeader>();
+        let size = std::mem::size_of::<TgaHeader>();
 
         assert_eq!(size, 18);
     }
+
+    #[test]
+    fn a1r5g5b5() {
+        assert_eq!(
+            Color::from_a1r5g5b5(0xfc00, true),
+            Color::rgba(255, 0, 0, 255)
+        );
+        assert_eq!(
+            Color::from_a1r5g5b5(0x03e0, true),
+            Color::rgba(0, 255, 0, 0)
+        );
+        assert_eq!(Color::from_a1r5g5b5(0x801f, false), Color::rgb(0, 0, 255));
+        assert_eq!(Color::rgba(132, 0, 255, 200).to_a1r5g5b5(), 0xc01f);
+    }
+
+    #[test]
+    fn write_read_16_bits() {
+        let mut image = Image::new(5, 4, 4);
+        image.set(0, 0, Color::rgba(255, 132, 0, 255));
+        image.set(4, 3, Color::rgba(0, 0, 255, 255));
+
+        let path = std::env::temp_dir().join("tga_image_write_read_16_bits.tga");
+        let options = WriteOptions::new()
+            .encoding(Encoding::Rle)
+            .bits_per_pixel(16);
+        image.write_tga_file(&path, options).unwrap();
+        let mut read = Image::read_tga_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        read.reorient(Orientation::BottomLeft);
+        assert_eq!(read, image);
+    }
+
+    #[test]
+    fn write_read_thumbnail() {
+        let mut image = Image::new(200, 100, 3);
+        for x in 0..200 {
+            image.set(x, x / 2, Color::rgb(x as u8, 255, 0));
+        }
+
+        let thumbnail = image.thumbnail();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (64, 32));
+        assert_eq!(thumbnail.format(), 3);
+
+        let path = std::env::temp_dir().join("tga_image_write_read_thumbnail.tga");
+        image
+            .write_tga_file(&path, WriteOptions::new().thumbnail(true))
+            .unwrap();
+        let mut read = Image::read_thumbnail(&path).unwrap().unwrap();
+        image.write_tga_file(&path, None).unwrap();
+        let missing = Image::read_thumbnail(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        read.reorient(Orientation::BottomLeft);
+        assert_eq!(read, thumbnail);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn write_read_image_id() {
+        let mut image = Image::new(3, 1, 3);
+        image.set(1, 0, Color::rgb(10, 20, 30));
+        assert!(image.set_id("flat shaded head"));
+        assert!(!image.set_id([0u8; 256]));
+
+        let path = std::env::temp_dir().join("tga_image_write_read_image_id.tga");
+        image.write_tga_file(&path, Encoding::Rle).unwrap();
+        let options = ReadOptions::new().orientation(None);
+        let read = Image::read_tga_file_with_options(&path, &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, image);
+        assert_eq!(read.id(), b"flat shaded head");
+    }
+
+    #[test]
+    fn write_read_bytes() {
+        let mut image = Image::new(6, 4, 4);
+        image.set(5, 3, Color::rgba(1, 2, 3, 4));
+        image.set_metadata(Metadata::builder().author_name("pipe").build());
+        let options = WriteOptions::new().encoding(Encoding::Rle).thumbnail(true);
+
+        let path = std::env::temp_dir().join("tga_image_write_read_bytes.tga");
+        image.write_tga_file(&path, options.clone()).unwrap();
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let bytes = image.to_bytes(options).unwrap();
+        assert_eq!(bytes, file);
+
+        let mut read = Image::from_bytes(&bytes).unwrap();
+        read.reorient(Orientation::BottomLeft);
+        assert_eq!(read.get(5, 3), image.get(5, 3));
+        assert_eq!(read.metadata().unwrap().author_name(), "pipe");
+    }
+
+    #[test]
+    fn write_read_orientation() {
+        let mut image = Image::new(3, 2, 3);
+        image.set(0, 0, Color::rgb(255, 0, 0));
+        image.set(2, 0, Color::rgb(0, 255, 0));
+        image.set_origin(640, 480);
+
+        let path = std::env::temp_dir().join("tga_image_write_read_orientation.tga");
+        let options = WriteOptions::new().orientation(Orientation::TopRight);
+        image.write_tga_file(&path, options).unwrap();
+        let kept = ReadOptions::new().orientation(None);
+        let stored = Image::read_tga_file_with_options(&path, &kept).unwrap();
+        let read = Image::read_tga_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(stored.orientation(), Orientation::TopRight);
+        assert_eq!(stored.origin(), (640, 480));
+        assert_eq!(stored.get(2, 1), Some(Color::rgb(255, 0, 0)));
+        assert_eq!(read.orientation(), Orientation::TopLeft);
+        assert_eq!(read.get(0, 1), Some(Color::rgb(255, 0, 0)));
+        assert_eq!(read.get(2, 1), Some(Color::rgb(0, 255, 0)));
+    }
+
+    #[test]
+    fn write_read_alpha_mode() {
+        let mut image = Image::new(2, 1, 4);
+        image.set(0, 0, Color::rgba(255, 128, 0, 128));
+
+        let bytes = image.to_bytes(None).unwrap();
+        assert_eq!(bytes[17] & 0x0f, 8);
+        let read = Image::from_bytes(&bytes).unwrap();
+        assert_eq!(read.alpha_mode(), AlphaMode::Straight);
+        assert!(read.metadata().is_none());
+        assert_eq!(Image::new(1, 1, 3).to_bytes(None).unwrap()[17] & 0x0f, 0);
+
+        image.premultiply();
+        assert_eq!(image.get(0, 0), Some(Color::rgba(128, 64, 0, 128)));
+        let read = Image::from_bytes(&image.to_bytes(None).unwrap()).unwrap();
+        assert_eq!(read.alpha_mode(), AlphaMode::Premultiplied);
+        assert_eq!(
+            read.metadata().unwrap().attributes_type(),
+            AttributesType::PremultipliedAlpha
+        );
+        assert_eq!(read.get(0, 0), Some(Color::rgba(128, 64, 0, 128)));
+        image.unpremultiply();
+        assert_eq!(image.alpha_mode(), AlphaMode::Straight);
+        assert_eq!(image.get(0, 0), Some(Color::rgba(255, 128, 0, 128)));
+
+        // older writers left the alpha bits out
+        let mut bytes = bytes;
+        bytes[17] &= 0xf0;
+        let read = Image::from_bytes(&bytes).unwrap();
+        assert_eq!(read.alpha_mode(), AlphaMode::Undefined);
+        assert_eq!(
+            ImageRef::from_bytes(&bytes).unwrap().alpha_mode(),
+            AlphaMode::Undefined
+        );
+        let read = Image::from_bytes(&read.to_bytes(None).unwrap()).unwrap();
+        assert_eq!(read.alpha_mode(), AlphaMode::Undefined);
+        assert_eq!(
+            read.metadata().unwrap().attributes_type(),
+            AttributesType::UndefinedRetain
+        );
+    }
+
+    #[test]
+    fn flip_empty() {
+        let mut image = Image::new(0, 3, 3);
+        image.flip_horizontally();
+        image.flip_vertically();
+        image.reorient(Orientation::TopRight);
+        image.rotate90();
+        assert_eq!((image.width(), image.height()), (3, 0));
+
+        let mut indexed =
+            IndexedImage::new(0, 2, ColorMap::new(0, vec![Color::rgb(1, 2, 3)]).unwrap());
+        indexed.flip_horizontally();
+
+        let bytes = image.to_bytes(Encoding::RleScanline).unwrap();
+        assert_eq!(bytes[12..16], [3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rotate_transpose() {
+        let mut image = Image::new(3, 2, 3);
+        image.reorient(Orientation::TopLeft);
+        for (i, byte) in image.as_mut_slice().iter_mut().enumerate() {
+            *byte = (i / 3) as u8;
+        }
+        let at = |image: &Image, x, y| image.get(x, y).unwrap().as_slice()[0];
+
+        let mut rotated = image.clone();
+        rotated.rotate90();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(at(&rotated, 0, 0), 3);
+        assert_eq!(at(&rotated, 1, 0), 0);
+        assert_eq!(at(&rotated, 0, 2), 5);
+        rotated.rotate270();
+        assert_eq!(rotated.as_slice(), image.as_slice());
+
+        let mut transposed = image.clone();
+        transposed.transpose();
+        assert_eq!(at(&transposed, 1, 2), 5);
+        assert_eq!(at(&transposed, 0, 1), 1);
+
+        let mut turned = image.clone();
+        turned.rotate180();
+        assert_eq!(at(&turned, 0, 0), 5);
+        assert_eq!(at(&turned, 2, 1), 0);
+
+        // the same as displayed however the pixels are stored
+        for &orientation in &[
+            Orientation::BottomLeft,
+            Orientation::BottomRight,
+            Orientation::TopRight,
+        ] {
+            let transforms: [fn(&mut Image); 4] = [
+                Image::rotate90,
+                Image::rotate180,
+                Image::rotate270,
+                Image::transpose,
+            ];
+            for transform in transforms.iter() {
+                let mut expected = image.clone();
+                transform(&mut expected);
+
+                let mut stored = image.clone();
+                stored.reorient(orientation);
+                transform(&mut stored);
+                stored.reorient(Orientation::TopLeft);
+                assert_eq!(stored.as_slice(), expected.as_slice());
+            }
+        }
+
+        // sizes without a fixed size copy move whole pixels the same way
+        let mut wide = Image::new(3, 2, 2);
+        wide.reorient(Orientation::TopLeft);
+        for (i, byte) in wide.as_mut_slice().iter_mut().enumerate() {
+            *byte = (i / 2) as u8;
+        }
+        wide.rotate90();
+        assert_eq!((wide.width(), wide.height()), (2, 3));
+        let mut expected = image.clone();
+        expected.rotate90();
+        let first_bytes = |image: &Image| {
+            let pixels = image.as_slice().chunks_exact(image.format());
+            pixels.map(|pixel| pixel[0]).collect::<Vec<_>>()
+        };
+        assert_eq!(first_bytes(&wide), first_bytes(&expected));
+        wide.rotate270();
+        assert_eq!(&wide.as_slice()[..4], &[0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn rle_packets() {
+        let mut seed = 7u32;
+        let data = (0..600)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                // long stretches of one value, then noise
+                if seed >> 30 == 0 {
+                    (seed >> 16) as u8 % 3
+                } else {
+                    0
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // every packet length for every prefix
+        let mut cost = vec![0usize; data.len() + 1];
+        for i in 1..=data.len() {
+            cost[i] = (1..=i.min(128))
+                .map(|length| {
+                    let pixels = &data[i - length..i];
+                    if pixels.iter().all(|&p| p == pixels[0]) {
+                        cost[i - length] + 2
+                    } else {
+                        cost[i - length] + 1 + length
+                    }
+                })
+                .min()
+                .unwrap();
+        }
+        let size =
+            |data: &[u8], bytes_pp| Image::rle_size(&Image::rle_packets(data, bytes_pp), bytes_pp);
+        assert_eq!(size(&data, 1), cost[data.len()]);
+
+        let mut encoded = Vec::new();
+        Image::save_rle_data(&data, 1, &mut encoded).unwrap();
+        assert_eq!(encoded.len(), cost[data.len()]);
+        let mut decoded = Vec::new();
+        Image::load_rle_data(&encoded[..], 1, data.len(), &mut decoded, &mut 0).unwrap();
+        assert_eq!(decoded, data);
+
+        // a repeat of 2 single byte pixels is cheaper left in a raw packet
+        let data = [1, 2, 2, 3, 4, 4, 5];
+        assert_eq!(size(&data, 1), 1 + data.len());
+        // but not with larger pixels
+        let data = [1, 2, 3, 1, 2, 3, 4, 5, 6];
+        assert_eq!(size(&data, 3), 4 + 4);
+
+        // packets don't cross from one chunk to the next
+        let data = vec![9u8; (RLE_CHUNK * 3 + 5) * 3];
+        let mut encoded = Vec::new();
+        Image::save_rle_data(&data, 3, &mut encoded).unwrap();
+        assert_eq!(encoded.len(), size(&data, 3));
+        let mut decoded = Vec::new();
+        Image::load_rle_data(&encoded[..], 3, data.len(), &mut decoded, &mut 0).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn write_auto_encoding() {
+        let mut image = Image::new(8, 4, 3);
+        assert_eq!(image.to_bytes(Encoding::Auto).unwrap()[2], 10);
+
+        for (i, byte) in image.as_mut_slice().iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        let bytes = image.to_bytes(Encoding::Auto).unwrap();
+        assert_eq!(bytes, image.to_bytes(None).unwrap());
+        assert_eq!(
+            Image::from_bytes(&bytes).unwrap().get(0, 3),
+            image.get(0, 0)
+        );
+        assert!(TgaEncoder::new(Vec::new(), 8, 4, 3, Encoding::Auto).is_err());
+    }
+
+    #[test]
+    fn write_read_scan_lines() {
+        let mut image = Image::new(5, 3, 3);
+        image.set(4, 0, Color::rgb(9, 9, 9));
+        image.set(0, 1, Color::rgb(0, 0, 255));
+
+        let bytes = image.to_bytes(Encoding::RleScanline).unwrap();
+        assert!(bytes.len() > image.to_bytes(Encoding::Rle).unwrap().len());
+
+        let kept = ReadOptions::new().orientation(None);
+        let read =
+            Image::from_seekable_reader_with_options(io::Cursor::new(&bytes), &kept).unwrap();
+        assert_eq!(read.as_slice(), image.as_slice());
+
+        let table = read.metadata().unwrap().scan_line_table();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[0], 18);
+        for (y, &offset) in table.iter().enumerate().rev() {
+            let mut row = [0u8; 15];
+            let mut packet = RlePacket::default();
+            packet.read(&bytes[offset as usize..], 3, &mut row).unwrap();
+            packet.finish().unwrap();
+            assert_eq!(row, image.as_slice()[y * 15..][..15]);
+        }
+
+        let mut encoder = TgaEncoder::new(Vec::new(), 5, 3, 3, Encoding::RleScanline).unwrap();
+        for row in image.as_slice().chunks_exact(15) {
+            encoder.write_row(row).unwrap();
+        }
+        assert_eq!(encoder.finish().unwrap(), bytes);
+    }
+
+    #[test]
+    fn read_lenient() {
+        let mut image = Image::new(4, 3, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                image.set(x, y, Color::rgb(255, 0, 0));
+            }
+        }
+        let options = ReadOptions::new()
+            .orientation(None)
+            .fill(Color::rgba(0, 255, 0, 255));
+
+        // a row and a half of pixels
+        let bytes = image.to_bytes(None).unwrap();
+        let truncated = &bytes[..18 + 12 + 6];
+        assert!(matches!(
+            Image::from_reader(truncated),
+            Err(TgaError::Truncated)
+        ));
+        let (read, warnings) = Image::from_reader_lenient(truncated, &options).unwrap();
+        assert!(matches!(warnings[..], [TgaError::Truncated]));
+        assert_eq!(read.get(1, 1), Some(Color::rgb(255, 0, 0)));
+        assert_eq!(read.get(2, 1), Some(Color::rgb(0, 255, 0)));
+        assert_eq!(read.get(3, 2), Some(Color::rgb(0, 255, 0)));
+
+        let (read, warnings) =
+            Image::from_seekable_reader_lenient(io::Cursor::new(&bytes), &ReadOptions::new())
+                .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(read.as_slice(), image.as_slice());
+
+        // a run of 13 pixels where 12 fit
+        let mut bytes = image.to_bytes(Encoding::Rle).unwrap();
+        bytes[18] = 0x8c;
+        let (read, warnings) = Image::from_reader_lenient(&bytes[..], &options).unwrap();
+        assert!(matches!(warnings[..], [TgaError::RleOverrun]));
+        assert_eq!(read.as_slice(), image.as_slice());
+
+        let color_map = ColorMap::new(0, vec![Color::rgb(0, 0, 255)]).unwrap();
+        let mut bytes = IndexedImage::new(2, 2, color_map).to_bytes(None).unwrap();
+        bytes[18 + 3] = 5;
+        assert!(Image::from_reader(&bytes[..]).is_err());
+        let (read, warnings) = Image::from_reader_lenient(&bytes[..], &options).unwrap();
+        assert!(matches!(warnings[..], [TgaError::BadColorMap(_)]));
+        assert_eq!(read.get(0, 0), Some(Color::rgb(0, 255, 0)));
+        assert_eq!(read.get(1, 0), Some(Color::rgb(0, 0, 255)));
+    }
 }
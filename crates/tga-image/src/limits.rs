@@ -0,0 +1,147 @@
+//! Limits on the size of images that are read
+
+use crate::{TgaError, TgaHeader};
+
+/// # Limits on the size of an image to read
+///
+/// The header of an image from an untrusted source can claim any size, these limits are checked
+/// before memory is allocated for the pixels. By default there are no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    max_width: usize,
+    max_height: usize,
+    max_bytes: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_width: usize::MAX,
+            max_height: usize::MAX,
+            max_bytes: usize::MAX,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Creates limits that allow any image
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest width allowed
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Sets the largest height allowed
+    pub fn max_height(mut self, max_height: usize) -> Self {
+        self.max_height = max_height;
+        self
+    }
+
+    /// Sets the largest size of the decoded pixels allowed, in bytes
+    ///
+    /// The scan line table and the fields of the developer area read after the pixels are also
+    /// limited to this size.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Checks the size of the image in `header`, 15 and 16 bit pixels count as 4 bytes as they
+    /// may be expanded to RGBA
+    pub(crate) fn check(&self, header: &TgaHeader) -> Result<(), TgaError> {
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let bytes_pp = if header.is_color_mapped() {
+            (header.color_map_depth as usize).div_ceil(8)
+        } else {
+            header.bytes_pp()
+        };
+        let bytes_pp = if bytes_pp == 2 { 4 } else { bytes_pp };
+
+        if width > self.max_width {
+            return Err(TgaError::LimitExceeded("width is over the limit"));
+        }
+        if height > self.max_height {
+            return Err(TgaError::LimitExceeded("height is over the limit"));
+        }
+        if width * height * bytes_pp > self.max_bytes {
+            return Err(TgaError::LimitExceeded("size in bytes is over the limit"));
+        }
+        Ok(())
+    }
+
+    /// Checks the size in bytes of what is read after the pixels
+    pub(crate) fn check_trailer(&self, bytes: u64) -> Result<(), TgaError> {
+        if bytes > self.max_bytes as u64 {
+            return Err(TgaError::LimitExceeded(
+                "size of the metadata is over the limit",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Color, ColorMap, Encoding, Image, IndexedImage, ReadOptions, TgaDecoder};
+
+    #[test]
+    fn decode_limits() {
+        let file = Image::new(40, 30, 3).to_bytes(None).unwrap();
+        let read =
+            |limits| Image::from_reader_with_options(&file[..], &ReadOptions::new().limits(limits));
+
+        assert!(read(DecodeLimits::new().max_width(40).max_height(30)).is_ok());
+        assert!(matches!(
+            read(DecodeLimits::new().max_width(39)),
+            Err(TgaError::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            read(DecodeLimits::new().max_height(29)),
+            Err(TgaError::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            read(DecodeLimits::new().max_bytes(40 * 30 * 3 - 1)),
+            Err(TgaError::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            TgaDecoder::with_limits(&file[..], DecodeLimits::new().max_width(10)),
+            Err(TgaError::LimitExceeded(_))
+        ));
+
+        let indexed =
+            IndexedImage::new(40, 30, ColorMap::new(0, vec![Color::rgb(1, 2, 3)]).unwrap());
+        let file = indexed.to_bytes(None).unwrap();
+        assert!(IndexedImage::from_reader_with_limits(&file[..], DecodeLimits::new()).is_ok());
+        assert!(matches!(
+            IndexedImage::from_reader_with_limits(&file[..], DecodeLimits::new().max_bytes(100)),
+            Err(TgaError::LimitExceeded(_))
+        ));
+
+        // the scan line table is also limited, a column of grey pixels needs 4 times the bytes
+        let file = Image::new(1, 30, 1)
+            .to_bytes(Encoding::RleScanline)
+            .unwrap();
+        let read = |limits| {
+            let options = ReadOptions::new().limits(limits);
+            Image::from_seekable_reader_lenient(std::io::Cursor::new(&file), &options).unwrap()
+        };
+        assert!(read(DecodeLimits::new()).1.is_empty());
+        let (image, warnings) = read(DecodeLimits::new().max_bytes(30));
+        assert!(image.metadata().is_none());
+        assert!(matches!(warnings[..], [TgaError::LimitExceeded(_)]));
+
+        // a tiny run length encoded file can claim to be huge, it is only allocated as it is read
+        #[rustfmt::skip]
+        let file = [
+            0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x7f, 0xff, 0x7f, 32, 8,
+            0xff, 1, 2, 3, 4,
+        ];
+        assert!(matches!(Image::from_bytes(&file), Err(TgaError::Truncated)));
+    }
+}
@@ -0,0 +1,382 @@
+//! Building color maps for true color images
+
+use std::collections::HashMap;
+
+use crate::{Color, ColorMap, Image, IndexedImage};
+
+const MAX_COLORS: usize = 256;
+
+/// How to pick the colors of a color map when an image has too many to keep them all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantizer {
+    /// - MedianCut: repeatedly split the group of colors with the widest channel at its median,
+    ///   good for images where a few colors dominate
+    MedianCut,
+    /// - Octree: merge the least used branches of an octree of colors, faster and keeps rarely
+    ///   used colors apart
+    Octree,
+}
+
+/// How pixels are mapped to a color map that doesn't contain their exact color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// - None: use the closest color
+    None,
+    /// - FloydSteinberg: use the closest color and spread the difference to the pixels to the
+    ///   right and below
+    FloydSteinberg,
+}
+
+/// A pixel as `[b, g, r, a]`, grey scale and RGB pixels have an alpha of 0
+type Key = [u8; 4];
+
+impl Image {
+    /// # Converts this image into a color mapped image
+    ///
+    /// If there are 256 or fewer colors the color map contains them exactly, otherwise
+    /// `quantizer` picks 256 colors and `dither` decides how the pixels are mapped to them.
+    ///
+    /// Grey scale images are given an RGB color map, RGBA images keep their alpha.
+    pub fn quantize(&self, quantizer: Quantizer, dither: Dither) -> IndexedImage {
+        let keys = self
+            .data
+            .chunks_exact(self.bytes_pp)
+            .map(|pixel| match *pixel {
+                [v] => [v, v, v, 0],
+                [b, g, r] => [b, g, r, 0],
+                [b, g, r, a] => [b, g, r, a],
+                _ => [0; 4],
+            })
+            .collect::<Vec<Key>>();
+
+        let mut histogram = Vec::new();
+        let mut lookup = HashMap::new();
+        for &key in &keys {
+            let index = *lookup.entry(key).or_insert_with(|| {
+                histogram.push((key, 0));
+                histogram.len() - 1
+            });
+            histogram[index].1 += 1;
+        }
+
+        let (palette, indices) = if histogram.len() <= MAX_COLORS {
+            let indices = keys.iter().map(|key| lookup[key] as u16).collect();
+            let palette = histogram.into_iter().map(|(key, _)| key).collect();
+            (palette, indices)
+        } else {
+            let palette = match quantizer {
+                Quantizer::MedianCut => median_cut(histogram),
+                Quantizer::Octree => octree(&histogram),
+            };
+            let indices = match dither {
+                Dither::None => map_nearest(&keys, &palette),
+                Dither::FloydSteinberg => floyd_steinberg(&keys, self.width, &palette),
+            };
+            (palette, indices)
+        };
+
+        // an empty image still needs a color for the color map
+        let palette = if palette.is_empty() {
+            vec![[0; 4]]
+        } else {
+            palette
+        };
+        let entries = palette
+            .into_iter()
+            .map(|[b, g, r, a]| {
+                if self.bytes_pp == 4 {
+                    Color::rgba(r, g, b, a)
+                } else {
+                    Color::rgb(r, g, b)
+                }
+            })
+            .collect();
+        let color_map = ColorMap::new(0, entries).expect("entries share a format");
+
+        IndexedImage::from_raw(
+            self.width,
+            self.height,
+            indices,
+            color_map,
+            self.orientation,
+        )
+    }
+}
+
+fn distance(a: Key, b: Key) -> u32 {
+    a.iter()
+        .zip(&b)
+        .map(|(&a, &b)| {
+            let d = a as i32 - b as i32;
+            (d * d) as u32
+        })
+        .sum()
+}
+
+fn nearest(key: Key, palette: &[Key]) -> u16 {
+    let (index, _) = palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &entry)| distance(key, entry))
+        .expect("palette is not empty");
+    index as u16
+}
+
+fn map_nearest(keys: &[Key], palette: &[Key]) -> Vec<u16> {
+    let mut cache = HashMap::new();
+    keys.iter()
+        .map(|&key| *cache.entry(key).or_insert_with(|| nearest(key, palette)))
+        .collect()
+}
+
+fn floyd_steinberg(keys: &[Key], width: usize, palette: &[Key]) -> Vec<u16> {
+    let mut cache = HashMap::new();
+    let mut indices = Vec::with_capacity(keys.len());
+    // errors are kept in sixteenths with a column of padding on each side
+    let mut current = vec![[0i32; 4]; width + 2];
+    let mut next = vec![[0i32; 4]; width + 2];
+
+    for row in keys.chunks_exact(width) {
+        for (x, key) in row.iter().enumerate() {
+            let mut wanted = [0i32; 4];
+            let mut adjusted = [0u8; 4];
+            for c in 0..4 {
+                wanted[c] = key[c] as i32 + current[x + 1][c] / 16;
+                adjusted[c] = wanted[c].clamp(0, 255) as u8;
+            }
+
+            let index = *cache
+                .entry(adjusted)
+                .or_insert_with(|| nearest(adjusted, palette));
+            indices.push(index);
+
+            let chosen = palette[index as usize];
+            for c in 0..4 {
+                let error = wanted[c] - chosen[c] as i32;
+                current[x + 2][c] += error * 7;
+                next[x][c] += error * 3;
+                next[x + 1][c] += error * 5;
+                next[x + 2][c] += error;
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|error| *error = [0; 4]);
+    }
+
+    indices
+}
+
+fn average(colors: &[(Key, u64)]) -> Key {
+    let mut sum = [0u64; 4];
+    let mut count = 0;
+    for &(key, n) in colors {
+        for c in 0..4 {
+            sum[c] += key[c] as u64 * n;
+        }
+        count += n;
+    }
+    let mut result = [0u8; 4];
+    for c in 0..4 {
+        result[c] = ((sum[c] + count / 2) / count.max(1)) as u8;
+    }
+    result
+}
+
+fn median_cut(mut colors: Vec<(Key, u64)>) -> Vec<Key> {
+    // each box is a range of `colors`
+    let mut boxes = vec![(0, colors.len())];
+
+    while boxes.len() < MAX_COLORS {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &(start, end))| {
+                let colors = &colors[start..end];
+                (0..4).map(move |c| {
+                    let channel = colors.iter().map(|(key, _)| key[c]);
+                    let range = channel.clone().max().unwrap() - channel.min().unwrap();
+                    (range, i, c)
+                })
+            })
+            .max_by_key(|&(range, ..)| range);
+
+        let (i, channel) = match widest {
+            Some((range, i, channel)) if range > 0 => (i, channel),
+            _ => break,
+        };
+
+        let (start, end) = boxes[i];
+        let slice = &mut colors[start..end];
+        slice.sort_unstable_by_key(|(key, _)| key[channel]);
+
+        let total: u64 = slice.iter().map(|&(_, n)| n).sum();
+        let mut seen = 0;
+        let mut split = slice.len() - 1;
+        for (k, &(_, n)) in slice.iter().enumerate() {
+            seen += n;
+            if seen * 2 >= total {
+                split = k + 1;
+                break;
+            }
+        }
+        let split = start + split.clamp(1, slice.len() - 1);
+
+        boxes[i] = (start, split);
+        boxes.push((split, end));
+    }
+
+    boxes
+        .into_iter()
+        .map(|(start, end)| average(&colors[start..end]))
+        .collect()
+}
+
+#[derive(Default)]
+struct OctreeNode {
+    // 0 is the root so it can never be a child
+    children: [usize; 8],
+    count: u64,
+    sum: [u64; 4],
+    leaf: bool,
+}
+
+fn octree(colors: &[(Key, u64)]) -> Vec<Key> {
+    const DEPTH: usize = 8;
+    let mut nodes = vec![OctreeNode::default()];
+    // the nodes that have children at each depth
+    let mut levels = vec![Vec::new(); DEPTH];
+    levels[0].push(0);
+    let mut leaves = 0;
+
+    for &(key, n) in colors {
+        let mut node = 0;
+        for depth in 0..=DEPTH {
+            nodes[node].count += n;
+            for (sum, &v) in nodes[node].sum.iter_mut().zip(&key) {
+                *sum += v as u64 * n;
+            }
+            if depth == DEPTH {
+                nodes[node].leaf = true;
+                break;
+            }
+
+            let shift = 7 - depth;
+            let child = (((key[2] >> shift) & 1) << 2
+                | ((key[1] >> shift) & 1) << 1
+                | ((key[0] >> shift) & 1)) as usize;
+            if nodes[node].children[child] == 0 {
+                nodes.push(OctreeNode::default());
+                nodes[node].children[child] = nodes.len() - 1;
+                if depth + 1 == DEPTH {
+                    leaves += 1;
+                } else {
+                    levels[depth + 1].push(nodes.len() - 1);
+                }
+            }
+            node = nodes[node].children[child];
+        }
+    }
+
+    // merge the least used nodes at the deepest level into single leaves
+    while leaves > MAX_COLORS {
+        let level = levels
+            .iter_mut()
+            .rev()
+            .find(|level| !level.is_empty())
+            .expect("the root can always be reduced");
+        let (position, _) = level
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &node)| nodes[node].count)
+            .unwrap();
+        let node = level.swap_remove(position);
+
+        let children = nodes[node].children.iter().filter(|&&c| c != 0).count();
+        leaves = leaves + 1 - children;
+        nodes[node].children = [0; 8];
+        nodes[node].leaf = true;
+    }
+
+    let mut palette = Vec::with_capacity(leaves);
+    let mut stack = vec![0];
+    while let Some(node) = stack.pop() {
+        let node = &nodes[node];
+        if node.leaf {
+            let mut key = [0u8; 4];
+            for (v, &sum) in key.iter_mut().zip(&node.sum) {
+                *v = ((sum + node.count / 2) / node.count) as u8;
+            }
+            palette.push(key);
+        } else {
+            stack.extend(node.children.iter().filter(|&&c| c != 0));
+        }
+    }
+    palette
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient() -> Image {
+        let mut image = Image::new(32, 32, 3);
+        for x in 0..32 {
+            for y in 0..32 {
+                image.set(x, y, Color::rgb(x as u8 * 8, y as u8 * 8, (x + y) as u8));
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn exact_color_map() {
+        let mut image = Image::new(8, 8, 4);
+        image.set(3, 4, Color::rgba(10, 20, 30, 40));
+        image.set(5, 1, Color::rgba(50, 60, 70, 80));
+
+        let indexed = image.quantize(Quantizer::MedianCut, Dither::FloydSteinberg);
+        assert_eq!(indexed.color_map().len(), 3);
+        assert_eq!(indexed.to_image(), image);
+    }
+
+    #[test]
+    fn quantized_color_map() {
+        let image = gradient();
+        let mean_squared_error = |quantized: &Image| {
+            let squares = image
+                .as_slice()
+                .iter()
+                .zip(quantized.as_slice())
+                .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as usize)
+                .sum::<usize>();
+            squares as f64 / image.as_slice().len() as f64
+        };
+
+        // random colors are never close to the color map so the error is always spread
+        let mut noise = Image::new(32, 32, 3);
+        let mut seed = 7u32;
+        for byte in noise.as_mut_slice() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *byte = (seed >> 16) as u8;
+        }
+
+        for &quantizer in &[Quantizer::MedianCut, Quantizer::Octree] {
+            for &dither in &[Dither::None, Dither::FloydSteinberg] {
+                let indexed = image.quantize(quantizer, dither);
+                assert!(indexed.color_map().len() <= MAX_COLORS);
+                let quantized = indexed.to_image();
+                assert_eq!(quantized.format(), 3);
+                // 1024 colors share 256 entries, so each channel is only a few levels off
+                assert!(mean_squared_error(&quantized) < 20.0);
+            }
+
+            let plain = noise.quantize(quantizer, Dither::None);
+            let dithered = noise.quantize(quantizer, Dither::FloydSteinberg);
+            assert_eq!(plain.color_map(), dithered.color_map());
+            assert_ne!(plain.indices(), dithered.indices());
+        }
+
+        let indexed = Image::new(0, 0, 3).quantize(Quantizer::Octree, Dither::None);
+        assert_eq!(indexed.color_map().len(), 1);
+    }
+}
@@ -0,0 +1,417 @@
+//! # Images with the pixel format in their type
+//!
+//! [`crate::Image`] decides its format when it is created, so using a [`Color`] of another format
+//! fails at runtime. An [`Image<P>`](Image) can only hold pixels of type `P` so getting and
+//! setting pixels can't fail. [`DynamicImage`] holds whichever format a file is stored in.
+
+use std::{fmt, path::Path};
+
+use crate::{Color, LumaWeights, Orientation, PixelFormat, TgaError, WriteOptions};
+
+/// # A type of pixel an [`Image`] can hold
+pub trait Pixel: Copy + Default + PartialEq + fmt::Debug {
+    /// The format the pixel is written in
+    const FORMAT: PixelFormat;
+
+    /// Converts a color of any format into this pixel, see [`Color::convert`]
+    fn from_color(color: Color) -> Self;
+
+    /// Converts this pixel into a color of [`Pixel::FORMAT`]
+    fn to_color(self) -> Color;
+}
+
+/// A grey scale pixel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Grey8(pub u8);
+
+/// An RGB pixel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Rgb8 {
+    /// Red
+    pub r: u8,
+    /// Green
+    pub g: u8,
+    /// Blue
+    pub b: u8,
+}
+
+/// An RGBA pixel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Rgba8 {
+    /// Red
+    pub r: u8,
+    /// Green
+    pub g: u8,
+    /// Blue
+    pub b: u8,
+    /// Alpha
+    pub a: u8,
+}
+
+/// An RGB pixel in the order TGA files store it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Bgr8 {
+    /// Blue
+    pub b: u8,
+    /// Green
+    pub g: u8,
+    /// Red
+    pub r: u8,
+}
+
+impl Pixel for Grey8 {
+    const FORMAT: PixelFormat = PixelFormat::Grey;
+
+    fn from_color(color: Color) -> Self {
+        Self(color.luminance(LumaWeights::default()))
+    }
+
+    fn to_color(self) -> Color {
+        Color::grey_scale(self.0)
+    }
+}
+
+impl Pixel for Rgb8 {
+    const FORMAT: PixelFormat = PixelFormat::Rgb;
+
+    fn from_color(color: Color) -> Self {
+        let [b, g, r, _] = color.to_rgb().bgra;
+        Self { r, g, b }
+    }
+
+    fn to_color(self) -> Color {
+        Color::rgb(self.r, self.g, self.b)
+    }
+}
+
+impl Pixel for Rgba8 {
+    const FORMAT: PixelFormat = PixelFormat::Rgba;
+
+    fn from_color(color: Color) -> Self {
+        let [b, g, r, a] = color.to_rgba(255).bgra;
+        Self { r, g, b, a }
+    }
+
+    fn to_color(self) -> Color {
+        Color::rgba(self.r, self.g, self.b, self.a)
+    }
+}
+
+impl Pixel for Bgr8 {
+    const FORMAT: PixelFormat = PixelFormat::Rgb;
+
+    fn from_color(color: Color) -> Self {
+        let [b, g, r, _] = color.to_rgb().bgra;
+        Self { b, g, r }
+    }
+
+    fn to_color(self) -> Color {
+        Color::rgb(self.r, self.g, self.b)
+    }
+}
+
+/// # An image of pixels of type `P`
+///
+/// The first row is the top of the image and each row goes from left to right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image<P> {
+    width: usize,
+    height: usize,
+    pixels: Vec<P>,
+}
+
+impl<P: Pixel> Image<P> {
+    /// Creates an image with every pixel set to the default, usually black
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![P::default(); width * height],
+        }
+    }
+
+    /// Creates an image from its pixels, row by row from the top
+    ///
+    /// ## returns
+    ///
+    /// - None if there aren't `width * height` pixels
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<P>) -> Option<Self> {
+        if pixels.len() != width * height {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Converts the pixels of an image, only the pixels are kept
+    pub fn from_image(image: &crate::Image) -> Self {
+        let mut reoriented;
+        let image = if image.orientation() == Orientation::TopLeft {
+            image
+        } else {
+            reoriented = image.clone();
+            reoriented.reorient(Orientation::TopLeft);
+            &reoriented
+        };
+
+        let pixels = image
+            .as_slice()
+            .chunks_exact(image.format())
+            .map(|pixel| P::from_color(Color::try_from_slice(pixel).unwrap_or_default()))
+            .collect();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+        }
+    }
+
+    /// Converts into an image of [`Pixel::FORMAT`], stored from the top left
+    pub fn to_image(&self) -> crate::Image {
+        let mut image = crate::Image::new(self.width, self.height, P::FORMAT.bytes_pp());
+        image.orientation = Orientation::TopLeft;
+        let bytes_pp = image.bytes_pp;
+        for (bytes, pixel) in image.data.chunks_exact_mut(bytes_pp).zip(&self.pixels) {
+            bytes.copy_from_slice(pixel.to_color().as_slice());
+        }
+        image
+    }
+
+    /// Reads an image from a file, converting it to `P`
+    ///
+    /// ## errors
+    ///
+    /// - see [`crate::Image::read_tga_file`]
+    pub fn read_tga_file<F>(filename: F) -> Result<Self, TgaError>
+    where
+        F: AsRef<Path>,
+    {
+        crate::Image::read_tga_file(filename).map(|image| Self::from_image(&image))
+    }
+
+    /// Writes an image to a file
+    ///
+    /// ## errors
+    ///
+    /// - see [`crate::Image::write_tga_file`]
+    pub fn write_tga_file<F, O>(&self, filename: F, options: O) -> Result<(), TgaError>
+    where
+        F: AsRef<Path>,
+        O: Into<WriteOptions>,
+    {
+        self.to_image().write_tga_file(filename, options)
+    }
+
+    /// The pixel at `x`, `y`
+    ///
+    /// ## Panics
+    ///
+    /// - if the pixel is outside the image
+    pub fn get(&self, x: usize, y: usize) -> P {
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Sets the pixel at `x`, `y`
+    ///
+    /// ## Panics
+    ///
+    /// - if the pixel is outside the image
+    pub fn set(&mut self, x: usize, y: usize, pixel: P) {
+        let index = self.index(x, y);
+        self.pixels[index] = pixel;
+    }
+
+    /// The pixel at `x`, `y`, None if it is outside the image
+    pub fn get_checked(&self, x: usize, y: usize) -> Option<P> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.get(x, y))
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Every pixel, row by row from the top
+    pub fn pixels(&self) -> &[P] {
+        &self.pixels
+    }
+
+    /// Every pixel, row by row from the top
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        &mut self.pixels
+    }
+
+    /// Converts every pixel to another type
+    pub fn convert<Q: Pixel>(&self) -> Image<Q> {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|&pixel| Q::from_color(pixel.to_color()))
+                .collect(),
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel {}, {} is outside the {}x{} image",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        x + y * self.width
+    }
+}
+
+/// # An image in whichever format it was stored in
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicImage {
+    /// - Grey8: a grey scale image
+    Grey8(Image<Grey8>),
+    /// - Rgb8: an RGB image
+    Rgb8(Image<Rgb8>),
+    /// - Rgba8: an RGBA image
+    Rgba8(Image<Rgba8>),
+}
+
+impl DynamicImage {
+    /// Converts the pixels of an image keeping its format, only the pixels are kept
+    pub fn from_image(image: &crate::Image) -> Self {
+        match image.pixel_format() {
+            Some(PixelFormat::Grey) => Self::Grey8(Image::from_image(image)),
+            Some(PixelFormat::Rgb) => Self::Rgb8(Image::from_image(image)),
+            _ => Self::Rgba8(Image::from_image(image)),
+        }
+    }
+
+    /// Reads an image from a file in the format it is stored in, color mapped and 15 and 16 bit
+    /// images are expanded to RGB or RGBA
+    ///
+    /// ## errors
+    ///
+    /// - see [`crate::Image::read_tga_file`]
+    pub fn read_tga_file<F>(filename: F) -> Result<Self, TgaError>
+    where
+        F: AsRef<Path>,
+    {
+        crate::Image::read_tga_file(filename).map(|image| Self::from_image(&image))
+    }
+
+    /// Reads an image from a buffer in memory, see [`DynamicImage::read_tga_file`]
+    ///
+    /// ## errors
+    ///
+    /// - see [`crate::Image::from_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TgaError> {
+        crate::Image::from_bytes(bytes).map(|image| Self::from_image(&image))
+    }
+
+    /// Converts into an image of the same format, stored from the top left
+    pub fn to_image(&self) -> crate::Image {
+        match self {
+            Self::Grey8(image) => image.to_image(),
+            Self::Rgb8(image) => image.to_image(),
+            Self::Rgba8(image) => image.to_image(),
+        }
+    }
+
+    /// Converts every pixel to `P`
+    pub fn to_typed<P: Pixel>(&self) -> Image<P> {
+        match self {
+            Self::Grey8(image) => image.convert(),
+            Self::Rgb8(image) => image.convert(),
+            Self::Rgba8(image) => image.convert(),
+        }
+    }
+
+    /// The format of the pixels
+    pub fn format(&self) -> PixelFormat {
+        match self {
+            Self::Grey8(_) => PixelFormat::Grey,
+            Self::Rgb8(_) => PixelFormat::Rgb,
+            Self::Rgba8(_) => PixelFormat::Rgba,
+        }
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Grey8(image) => image.width(),
+            Self::Rgb8(image) => image.width(),
+            Self::Rgba8(image) => image.width(),
+        }
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        match self {
+            Self::Grey8(image) => image.height(),
+            Self::Rgb8(image) => image.height(),
+            Self::Rgba8(image) => image.height(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typed_images() {
+        let mut image = Image::<Rgb8>::new(3, 2);
+        image.set(2, 0, Rgb8 { r: 255, g: 0, b: 0 });
+        assert_eq!(image.get(2, 0), Rgb8 { r: 255, g: 0, b: 0 });
+        assert_eq!(image.get_checked(3, 0), None);
+
+        let bytes = image.to_image().to_bytes(None).unwrap();
+        let read = DynamicImage::from_bytes(&bytes).unwrap();
+        assert_eq!(read.format(), PixelFormat::Rgb);
+        assert_eq!(read, DynamicImage::Rgb8(image.clone()));
+
+        let bgr = read.to_typed::<Bgr8>();
+        assert_eq!(bgr.get(2, 0), Bgr8 { b: 0, g: 0, r: 255 });
+        let grey = image.convert::<Grey8>();
+        assert_eq!(grey.get(2, 0), Grey8(76));
+        let rgba = grey.convert::<Rgba8>();
+        assert_eq!(
+            rgba.get(2, 0),
+            Rgba8 {
+                r: 76,
+                g: 76,
+                b: 76,
+                a: 255
+            }
+        );
+
+        let mut stored = crate::Image::new(3, 2, 4);
+        stored.set(0, 0, Color::rgba(1, 2, 3, 4));
+        let typed = Image::<Rgba8>::from_image(&stored);
+        assert_eq!(typed.get(0, 1), Rgba8::from_color(Color::rgba(1, 2, 3, 4)));
+        assert_eq!(typed.to_image().get(0, 1), Some(Color::rgba(1, 2, 3, 4)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn typed_out_of_bounds() {
+        Image::<Grey8>::new(2, 2).set(0, 2, Grey8(1));
+    }
+}
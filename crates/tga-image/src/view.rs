@@ -0,0 +1,490 @@
+//! Working on rectangular parts of an image without copying them
+
+use std::ops::Range;
+
+use crate::{Color, Image, Orientation};
+
+/// # A rectangular part of an image, borrowed from its pixels
+///
+/// The coordinates are relative to the first pixel of the view in the order the pixels are
+/// stored, the same as [`Image::get`] and [`Image::composite`], so `y` counts up from the bottom
+/// of an image with a bottom orientation. The view keeps the orientation of its image. Each row
+/// is found `stride` bytes after the last so no pixels are copied to make the view.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a> {
+    width: usize,
+    height: usize,
+    bytes_pp: usize,
+    stride: usize,
+    orientation: Orientation,
+    data: &'a [u8],
+}
+
+/// # A rectangular part of an image that can be changed, see [`ImageView`]
+#[derive(Debug)]
+pub struct ImageViewMut<'a> {
+    width: usize,
+    height: usize,
+    bytes_pp: usize,
+    stride: usize,
+    orientation: Orientation,
+    data: &'a mut [u8],
+}
+
+/// The range of bytes covered by a part of a `size` view or image, with rows `stride` bytes
+/// apart
+///
+/// ## returns
+///
+/// - None if the part doesn't fit inside
+fn view_range(
+    size: (usize, usize),
+    stride: usize,
+    bytes_pp: usize,
+    (x, y): (usize, usize),
+    (width, height): (usize, usize),
+) -> Option<Range<usize>> {
+    if x.checked_add(width)? > size.0 || y.checked_add(height)? > size.1 {
+        return None;
+    }
+    if width == 0 || height == 0 {
+        return Some(0..0);
+    }
+    let start = y * stride + x * bytes_pp;
+    Some(start..start + (height - 1) * stride + width * bytes_pp)
+}
+
+impl<'a> ImageView<'a> {
+    /// Access a single pixel within the view
+    ///
+    /// ## returns
+    ///
+    /// - Some(Color) if the indicies are inside the view
+    /// - None otherwise
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        self.row(y)
+            .filter(|_| x < self.width)
+            .and_then(|row| Color::try_from_slice(&row[x * self.bytes_pp..][..self.bytes_pp]))
+    }
+
+    /// The bytes of a single row
+    ///
+    /// ## returns
+    ///
+    /// - Some(row) if the row is inside the view
+    /// - None otherwise
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        if self.width == 0 {
+            // the data of a view 0 pixels wide is empty, whatever its stride
+            return Some(&[]);
+        }
+        Some(&self.data[y * self.stride..][..self.width * self.bytes_pp])
+    }
+
+    /// Iterates over the bytes of each row
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let view = *self;
+        (0..self.height).filter_map(move |y| view.row(y))
+    }
+
+    /// A part of this view
+    ///
+    /// ## returns
+    ///
+    /// - None if the part doesn't fit inside this view
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<ImageView<'a>> {
+        let range = view_range(
+            (self.width, self.height),
+            self.stride,
+            self.bytes_pp,
+            (x, y),
+            (width, height),
+        )?;
+        Some(ImageView {
+            width,
+            height,
+            data: &self.data[range],
+            ..*self
+        })
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of bytes per pixel
+    pub fn format(&self) -> usize {
+        self.bytes_pp
+    }
+
+    /// Getter for the orientation of the image the view borrows from
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Copies the pixels into a new image with the same orientation, otherwise like
+    /// [`Image::new`]
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height, self.bytes_pp);
+        image.orientation = self.orientation;
+        image.copy_from(self, 0, 0);
+        image
+    }
+}
+
+impl<'a> ImageViewMut<'a> {
+    /// Borrows the view without being able to change it
+    pub fn as_view(&self) -> ImageView<'_> {
+        ImageView {
+            width: self.width,
+            height: self.height,
+            bytes_pp: self.bytes_pp,
+            stride: self.stride,
+            orientation: self.orientation,
+            data: self.data,
+        }
+    }
+
+    /// A part of this view that can be changed, see [`ImageView::view`]
+    pub fn view_mut(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<ImageViewMut<'_>> {
+        let range = view_range(
+            (self.width, self.height),
+            self.stride,
+            self.bytes_pp,
+            (x, y),
+            (width, height),
+        )?;
+        Some(ImageViewMut {
+            width,
+            height,
+            bytes_pp: self.bytes_pp,
+            stride: self.stride,
+            orientation: self.orientation,
+            data: &mut self.data[range],
+        })
+    }
+
+    /// Access a single pixel within the view, see [`ImageView::get`]
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        self.as_view().get(x, y)
+    }
+
+    /// Sets a single pixel to a color
+    ///
+    /// ## returns
+    ///
+    /// - true if this color matches the format of the view and is inside the view
+    /// - false otherwise, see [`Color::convert`] to match the format
+    pub fn set(&mut self, x: usize, y: usize, color: Color) -> bool {
+        let bytes_pp = self.bytes_pp;
+        match self.row_mut(y) {
+            Some(row) if x * bytes_pp < row.len() && color.as_slice().len() == bytes_pp => {
+                row[x * bytes_pp..][..bytes_pp].copy_from_slice(color.as_slice());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The bytes of a single row that can be changed
+    ///
+    /// ## returns
+    ///
+    /// - Some(row) if the row is inside the view
+    /// - None otherwise
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        if self.width == 0 {
+            return Some(&mut []);
+        }
+        Some(&mut self.data[y * self.stride..][..self.width * self.bytes_pp])
+    }
+
+    /// Sets every pixel of the view to a color
+    ///
+    /// ## returns
+    ///
+    /// - true if this color matches the format of the view
+    /// - false otherwise, nothing is changed
+    pub fn fill(&mut self, color: Color) -> bool {
+        if color.as_slice().len() != self.bytes_pp {
+            return false;
+        }
+        for y in 0..self.height {
+            let row = self.row_mut(y).expect("the row is inside the view");
+            for pixel in row.chunks_exact_mut(color.as_slice().len()) {
+                pixel.copy_from_slice(color.as_slice());
+            }
+        }
+        true
+    }
+
+    /// Copies the pixels of `source` into this view, with its first pixel at `x`, `y`
+    ///
+    /// `source` is flipped to the orientation of this view first, so it looks the same once
+    /// written. The parts of `source` outside this view are ignored.
+    ///
+    /// ## returns
+    ///
+    /// - true if `source` has the same format as this view
+    /// - false otherwise, nothing is copied
+    pub fn copy_from(&mut self, source: &ImageView, x: usize, y: usize) -> bool {
+        if source.bytes_pp != self.bytes_pp {
+            return false;
+        }
+        let width = source.width.min(self.width.saturating_sub(x));
+        let height = source.height.min(self.height.saturating_sub(y));
+        if width == 0 {
+            return true;
+        }
+        let flip_rows = source.orientation.is_top() != self.orientation.is_top();
+        let flip_pixels = source.orientation.is_right() != self.orientation.is_right();
+        let bytes_pp = self.bytes_pp;
+        for j in 0..height {
+            let bytes = width * bytes_pp;
+            let row = self.row_mut(y + j).expect("the row is inside the view");
+            let row = &mut row[x * bytes_pp..][..bytes];
+            let source_j = if flip_rows { source.height - 1 - j } else { j };
+            let source_row = source.row(source_j).expect("the row is inside the source");
+            if flip_pixels {
+                // the last `width` pixels of the source come first once flipped
+                let source_row = &source_row[(source.width - width) * bytes_pp..];
+                for (pixel, source_pixel) in row
+                    .chunks_exact_mut(bytes_pp)
+                    .zip(source_row.chunks_exact(bytes_pp).rev())
+                {
+                    pixel.copy_from_slice(source_pixel);
+                }
+            } else {
+                row.copy_from_slice(&source_row[..bytes]);
+            }
+        }
+        true
+    }
+
+    /// Getter for the width
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Getter for the height
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of bytes per pixel
+    pub fn format(&self) -> usize {
+        self.bytes_pp
+    }
+
+    /// Getter for the orientation of the image the view borrows from
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+}
+
+impl Image {
+    /// Borrows the whole image as a view
+    pub fn as_view(&self) -> ImageView<'_> {
+        ImageView {
+            width: self.width,
+            height: self.height,
+            bytes_pp: self.bytes_pp,
+            stride: self.width * self.bytes_pp,
+            orientation: self.orientation,
+            data: &self.data,
+        }
+    }
+
+    /// Borrows the whole image as a view that can be changed
+    pub fn as_view_mut(&mut self) -> ImageViewMut<'_> {
+        ImageViewMut {
+            width: self.width,
+            height: self.height,
+            bytes_pp: self.bytes_pp,
+            stride: self.width * self.bytes_pp,
+            orientation: self.orientation,
+            data: &mut self.data,
+        }
+    }
+
+    /// Borrows a rectangular part of the image, `x` and `y` are the same as [`Image::get`]
+    ///
+    /// ## returns
+    ///
+    /// - None if the part doesn't fit inside the image
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<ImageView<'_>> {
+        self.as_view().view(x, y, width, height)
+    }
+
+    /// Borrows a rectangular part of the image that can be changed, see [`Image::view`]
+    pub fn view_mut(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<ImageViewMut<'_>> {
+        let stride = self.width * self.bytes_pp;
+        let range = view_range(
+            (self.width, self.height),
+            stride,
+            self.bytes_pp,
+            (x, y),
+            (width, height),
+        )?;
+        Some(ImageViewMut {
+            width,
+            height,
+            bytes_pp: self.bytes_pp,
+            stride,
+            orientation: self.orientation,
+            data: &mut self.data[range],
+        })
+    }
+
+    /// Copies a rectangular part of the image, see [`Image::view`]
+    ///
+    /// The copy has the same format, orientation and alpha mode, and no ID, metadata or
+    /// developer area.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Option<Image> {
+        let mut image = self.view(x, y, width, height)?.to_image();
+        image.alpha_mode = self.alpha_mode;
+        Some(image)
+    }
+
+    /// Copies the pixels of `source` into the image, with its first pixel at `x`, `y`, see
+    /// [`ImageViewMut::copy_from`]
+    pub fn copy_from(&mut self, source: &ImageView, x: usize, y: usize) -> bool {
+        self.as_view_mut().copy_from(source, x, y)
+    }
+
+    /// Sets every pixel of a rectangle to a color, the parts outside the image are ignored
+    ///
+    /// `x` and `y` are the same as [`Image::get`].
+    ///
+    /// ## returns
+    ///
+    /// - true if this color matches the format of this image
+    /// - false otherwise, see [`Color::convert`] to match the format
+    pub fn fill_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        color: Color,
+    ) -> bool {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        match self.view_mut(x.min(self.width), y.min(self.height), width, height) {
+            Some(mut view) => view.fill(color),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn views() {
+        let mut sheet = Image::new(4, 3, 3);
+        for (i, byte) in sheet.as_mut_slice().iter_mut().enumerate() {
+            *byte = (i / 3) as u8;
+        }
+        let at = |image: &Image, x, y| image.get(x, y).unwrap().as_slice()[0];
+
+        let view = sheet.view(1, 1, 2, 2).unwrap();
+        assert_eq!(view.get(0, 0), Some(Color::rgb(5, 5, 5)));
+        assert_eq!(view.get(1, 1), Some(Color::rgb(10, 10, 10)));
+        assert_eq!(view.get(2, 0), None);
+        assert_eq!(view.rows().count(), 2);
+        assert_eq!(
+            view.view(1, 0, 1, 2).unwrap().get(0, 1),
+            Some(Color::rgb(10, 10, 10))
+        );
+        assert!(view.view(1, 1, 2, 1).is_none());
+        assert!(sheet.view(3, 0, 2, 1).is_none());
+
+        let tile = sheet.crop(1, 1, 2, 2).unwrap();
+        assert_eq!((tile.width(), tile.height()), (2, 2));
+        assert_eq!(at(&tile, 1, 0), 6);
+        assert!(sheet.crop(0, 0, 5, 1).is_none());
+
+        let mut atlas = Image::new(5, 4, 3);
+        assert!(atlas.copy_from(&tile.as_view(), 0, 0));
+        assert!(atlas.copy_from(&sheet.view(0, 0, 2, 2).unwrap(), 4, 3));
+        assert_eq!(at(&atlas, 1, 1), 10);
+        assert_eq!(at(&atlas, 4, 3), 0);
+        assert_eq!(at(&atlas, 2, 0), 0);
+        assert!(!atlas.copy_from(&Image::new(1, 1, 4).as_view(), 0, 0));
+
+        assert!(atlas.fill_rect(3, 2, 9, 9, Color::rgb(1, 2, 3)));
+        assert_eq!(atlas.get(4, 3), Some(Color::rgb(1, 2, 3)));
+        assert_eq!(atlas.get(2, 2), Some(Color::rgb(0, 0, 0)));
+        assert!(!atlas.fill_rect(0, 0, 1, 1, Color::grey_scale(1)));
+        assert!(atlas.fill_rect(7, 7, 1, 1, Color::rgb(1, 2, 3)));
+        assert!(atlas.fill_rect(7, 0, 1, 3, Color::rgb(1, 2, 3)));
+        assert_eq!(atlas.view(0, 0, 0, 2).unwrap().rows().count(), 2);
+        assert_eq!(atlas.view(0, 0, 0, 2).unwrap().row(1), Some(&[][..]));
+
+        let mut view = atlas.view_mut(0, 0, 2, 2).unwrap();
+        assert!(view.set(1, 1, Color::rgb(9, 9, 9)));
+        assert!(!view.set(2, 1, Color::rgb(9, 9, 9)));
+        view.view_mut(0, 1, 1, 1).unwrap().fill(Color::rgb(7, 7, 7));
+        assert_eq!(atlas.get(1, 1), Some(Color::rgb(9, 9, 9)));
+        assert_eq!(atlas.get(0, 1), Some(Color::rgb(7, 7, 7)));
+    }
+
+    #[test]
+    fn copy_between_orientations() {
+        let mut tile = Image::new(1, 2, 3);
+        tile.set(0, 0, Color::rgb(1, 1, 1));
+        tile.set(0, 1, Color::rgb(2, 2, 2));
+        tile.reorient(Orientation::TopLeft);
+        let tile = Image::from_bytes(&tile.to_bytes(None).unwrap()).unwrap();
+        assert_eq!(tile.orientation(), Orientation::TopLeft);
+
+        let mut copy = Image::new(1, 2, 3);
+        assert!(copy.copy_from(&tile.as_view(), 0, 0));
+        assert_eq!(copy.get(0, 0), tile.get(0, 1));
+        let read = Image::from_bytes(&copy.to_bytes(None).unwrap()).unwrap();
+        assert_eq!(read.as_slice(), tile.as_slice());
+
+        let mut strip = Image::new(3, 1, 3);
+        for x in 0..3 {
+            strip.set(x, 0, Color::rgb(x as u8, 0, 0));
+        }
+        let mut right = Image::new(2, 2, 3);
+        right.reorient(Orientation::TopRight);
+        assert!(right.copy_from(&strip.view(1, 0, 2, 1).unwrap(), 0, 1));
+        assert_eq!(right.get(0, 1), Some(Color::rgb(2, 0, 0)));
+        assert_eq!(right.get(1, 1), Some(Color::rgb(1, 0, 0)));
+        assert!(right.copy_from(&strip.as_view(), 1, 0));
+        assert_eq!(right.get(1, 0), Some(Color::rgb(2, 0, 0)));
+        assert_eq!(right.get(0, 0), Some(Color::rgb(0, 0, 0)));
+
+        let crop = right.crop(0, 1, 2, 1).unwrap();
+        assert_eq!(crop.orientation(), Orientation::TopRight);
+        assert_eq!(crop.get(0, 0), Some(Color::rgb(2, 0, 0)));
+    }
+}